@@ -18,3 +18,12 @@ dfx canister --network local call ic-canister-assets business_hashed_update "(tr
 
 # 上传资源文件
 RUST_BACKTRACE=1 cargo test upload -- --nocapture
+
+# 同步副本: 用同一个 wasm 再部署一个罐子, 源罐子授权副本读取后设置源罐子
+# 副本调用源罐子的 business_canister_changes_since / business_canister_changes_snapshot / business_canister_download_chunk_by_hash_certified, 都需要 BusinessCanisterRead 权限
+# dfx canister --network local create ic-canister-assets-replica
+# dfx canister --network local install ic-canister-assets-replica --wasm sources/source_opt.wasm
+# dfx canister --network local call ic-canister-assets permission_update "(vec { variant { UpdateUserPermission = record { principal \"$(dfx canister --network local id ic-canister-assets-replica)\"; opt vec { \"BusinessCanisterRead\" } } } })"
+# dfx canister --network local call ic-canister-assets-replica business_replication_update "(record { source = opt principal \"$(dfx canister --network local id ic-canister-assets)\"; batch = 100 : nat32 })"
+# dfx canister --network local call ic-canister-assets-replica schedule_trigger "()"
+# dfx canister --network local call ic-canister-assets-replica business_replication_status "()"
@@ -1,6 +1,8 @@
 #[allow(unused)]
 use ic_canister_kit::common::once::call_once_guard;
 #[allow(unused)]
+use ic_canister_kit::common::option::display_option;
+#[allow(unused)]
 use ic_canister_kit::identity::caller;
 
 #[allow(unused)]
@@ -37,12 +39,47 @@ fn business_hashed_update(hashed: bool) {
     )
 }
 
+// 查询
+#[ic_cdk::query(guard = "has_business_upload")]
+fn business_verify_find() -> bool {
+    with_state(|s| s.business_verify_find())
+}
+#[ic_cdk::update(guard = "has_business_upload")]
+fn business_verify_update(verify: bool) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_verify_find());
+
+    if old == verify {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set verify: {old} -> {verify}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_verify_update(verify);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Security.topic(),
+        arg_content,
+    )
+}
+
 // 查询
 #[ic_cdk::query(guard = "has_business_query")]
 fn business_files() -> Vec<QueryFile> {
     with_state(|s| s.business_files())
 }
 
+// 存储统计, 包括去重效果和内存占用
+#[ic_cdk::query(guard = "has_business_query")]
+fn business_stats() -> QueryStats {
+    with_state(|s| s.business_stats())
+}
+
 #[ic_cdk::query(guard = "has_business_query")]
 fn business_download(path: String) -> Vec<u8> {
     with_state(|s| s.business_download(path))
@@ -54,24 +91,123 @@ fn business_download_by(path: String, offset: u64, size: u64) -> Vec<u8> {
     with_state(|s| s.business_download_by(path, offset, size))
 }
 
+#[ic_cdk::query(guard = "has_business_query")]
+fn business_download_by_hash(hash: HashDigest) -> Vec<u8> {
+    with_state(|s| s.business_download_by_hash(hash))
+}
+
+// 分块下载, 先获取文件信息和推荐的块大小, 再逐块下载
+#[ic_cdk::query(guard = "has_business_query")]
+fn business_download_start(
+    path: String,
+    encoding: Option<String>,
+) -> Result<QueryDownloading, DownloadingError> {
+    with_state(|s| s.business_download_start(path, encoding))
+}
+
+#[ic_cdk::query(guard = "has_business_query")]
+fn business_download_chunk(arg: DownloadingArg) -> Result<Vec<u8>, DownloadingError> {
+    with_state(|s| s.business_download_chunk(arg))
+}
+
+// 其他罐子读取, 需要授权 BusinessCanisterRead 权限
+#[ic_cdk::query(composite = true, guard = "has_business_canister_read")]
+fn business_canister_file(path: String) -> Option<QueryFile> {
+    with_state(|s| s.business_file(path))
+}
+
+#[ic_cdk::query(composite = true, guard = "has_business_canister_read")]
+fn business_canister_file_by_hash(hash: HashDigest) -> Option<QueryFile> {
+    with_state(|s| s.business_file_by_hash(hash))
+}
+
+#[ic_cdk::query(composite = true, guard = "has_business_canister_read")]
+fn business_canister_download_start(
+    path: String,
+    encoding: Option<String>,
+) -> Result<QueryDownloading, DownloadingError> {
+    with_state(|s| s.business_download_start(path, encoding))
+}
+
+#[ic_cdk::query(composite = true, guard = "has_business_canister_read")]
+fn business_canister_download_chunk(arg: DownloadingArg) -> Result<Vec<u8>, DownloadingError> {
+    with_state(|s| s.business_download_chunk(arg))
+}
+
+#[ic_cdk::query(composite = true, guard = "has_business_canister_read")]
+fn business_canister_download_chunk_by_hash(
+    arg: DownloadingHashArg,
+) -> Result<Vec<u8>, DownloadingError> {
+    with_state(|s| s.business_download_chunk_by_hash(arg))
+}
+
+// 更新调用的返回结果经过共识, 对调用方来说是可信的
+#[ic_cdk::update(guard = "has_business_canister_read")]
+fn business_canister_download_chunk_certified(
+    arg: DownloadingArg,
+) -> Result<Vec<u8>, DownloadingError> {
+    with_state(|s| s.business_download_chunk(arg))
+}
+
+#[ic_cdk::update(guard = "has_business_canister_read")]
+fn business_canister_download_chunk_by_hash_certified(
+    arg: DownloadingHashArg,
+) -> Result<Vec<u8>, DownloadingError> {
+    with_state(|s| s.business_download_chunk_by_hash(arg))
+}
+
+// 变更记录, 用于同步镜像
+#[ic_cdk::query(guard = "has_business_query")]
+fn business_changes_since(since: u64, limit: u32) -> Result<QueryChanges, ChangesError> {
+    with_state(|s| s.business_changes_since(since, limit))
+}
+
+#[ic_cdk::query(guard = "has_business_query")]
+fn business_changes_snapshot(after: Option<String>, limit: u32) -> QueryChangesSnapshot {
+    with_state(|s| s.business_changes_snapshot(after, limit))
+}
+
+// 镜像罐子在定时任务中调用, 不能是 composite query
+#[ic_cdk::query(guard = "has_business_canister_read")]
+fn business_canister_changes_since(since: u64, limit: u32) -> Result<QueryChanges, ChangesError> {
+    with_state(|s| s.business_changes_since(since, limit))
+}
+
+#[ic_cdk::query(guard = "has_business_canister_read")]
+fn business_canister_changes_snapshot(after: Option<String>, limit: u32) -> QueryChangesSnapshot {
+    with_state(|s| s.business_changes_snapshot(after, limit))
+}
+
+// 查询上传中的文件, 续传时可以比较已上传的块
+#[ic_cdk::query(guard = "has_business_upload")]
+fn business_uploading(path: String, encoding: Option<String>) -> Option<QueryUploading> {
+    with_state(|s| s.business_uploading(path, encoding))
+}
+
 // 修改
 #[ic_cdk::update(guard = "has_business_upload")]
-fn business_upload(args: Vec<UploadingArg>) {
+fn business_upload(args: Vec<UploadingArg>) -> Result<(), UploadingError> {
     let _guard = call_once_guard(); // post 接口应该拦截
 
     let caller = caller();
     let arg_content = format!(
         "upload file: [{}]",
         args.iter()
-            .map(|arg| format!("path: {} size: {} index: {}", arg.path, arg.size, arg.index))
+            .map(|arg| format!(
+                "path: {} encoding: {} size: {} index: {}",
+                arg.path,
+                display_option(&arg.encoding),
+                arg.size,
+                arg.index
+            ))
             .collect::<Vec<_>>()
             .join(", ")
     ); // * 记录参数内容
 
     with_mut_state(
         |s| {
-            s.business_upload(args);
-            (None, ())
+            let result = s.business_upload(args);
+            (result.as_ref().err().map(|e| e.to_string()), result)
         },
         caller,
         RecordTopics::UploadFile.topic(),
@@ -96,3 +232,427 @@ fn business_delete(names: Vec<String>) {
         arg_content,
     )
 }
+
+// 查询被隔离的数据
+#[ic_cdk::query(guard = "has_business_query")]
+fn business_quarantined() -> Vec<QueryQuarantined> {
+    with_state(|s| s.business_quarantined())
+}
+
+#[ic_cdk::update(guard = "has_business_delete")]
+fn business_quarantine_delete(hashes: Vec<HashDigest>) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let caller = caller();
+    let arg_content = format!(
+        "delete quarantined: [{}]",
+        hashes
+            .iter()
+            .map(|hash| hash.hex())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ); // * 记录参数内容
+
+    with_mut_state(
+        |s: &mut State| {
+            s.business_quarantine_delete(hashes);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Security.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_routing_find() -> RoutingSettings {
+    with_state(|s| s.business_routing_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_routing_update(routing: RoutingSettings) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_routing_find());
+
+    if old == routing {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set routing: {old:?} -> {routing:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_routing_update(routing);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_error_pages_find() -> Vec<ErrorPage> {
+    with_state(|s| s.business_error_pages_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_error_pages_update(error_pages: Vec<ErrorPage>) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_error_pages_find());
+
+    if old == error_pages {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set error pages: {old:?} -> {error_pages:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_error_pages_update(error_pages);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_maintenance_find() -> MaintenanceSettings {
+    with_state(|s| s.business_maintenance_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_maintenance_update(maintenance: MaintenanceSettings) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_maintenance_find());
+
+    if old == maintenance {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set maintenance: {old:?} -> {maintenance:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_maintenance_update(maintenance);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_route_rules_find() -> Vec<RouteRule> {
+    with_state(|s| s.business_route_rules_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_route_rules_update(route_rules: Vec<RouteRule>) -> Result<(), String> {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_route_rules_find());
+
+    if old == route_rules {
+        return Ok(());
+    }
+
+    let caller = caller();
+    let arg_content = format!("set route rules: {old:?} -> {route_rules:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            let result = s.business_route_rules_update(route_rules);
+            (result.as_ref().err().cloned(), result)
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_header_rules_find() -> Vec<HeaderRule> {
+    with_state(|s| s.business_header_rules_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_header_rules_update(header_rules: Vec<HeaderRule>) -> Result<(), String> {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_header_rules_find());
+
+    if old == header_rules {
+        return Ok(());
+    }
+
+    let caller = caller();
+    let arg_content = format!("set header rules: {old:?} -> {header_rules:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            let result = s.business_header_rules_update(header_rules);
+            (result.as_ref().err().cloned(), result)
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_cors_rules_find() -> Vec<CorsRule> {
+    with_state(|s| s.business_cors_rules_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_cors_rules_update(cors_rules: Vec<CorsRule>) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_cors_rules_find());
+
+    if old == cors_rules {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set cors rules: {old:?} -> {cors_rules:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_cors_rules_update(cors_rules);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_explore_find() -> ExploreSettings {
+    with_state(|s| s.business_explore_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_explore_update(explore: ExploreSettings) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_explore_find());
+
+    if old == explore {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set explore: {old:?} -> {explore:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_explore_update(explore);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_manifest_find() -> ManifestSettings {
+    with_state(|s| s.business_manifest_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_manifest_update(manifest: ManifestSettings) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_manifest_find());
+
+    if old == manifest {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set manifest: {old:?} -> {manifest:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_manifest_update(manifest);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_change_retention_find() -> u64 {
+    with_state(|s| s.business_change_retention_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_change_retention_update(retention: u64) -> Result<(), String> {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_change_retention_find());
+
+    if old == retention {
+        return Ok(());
+    }
+
+    let caller = caller();
+    let arg_content = format!("set change retention: {old} -> {retention}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            let result = s.business_change_retention_update(retention);
+            (result.as_ref().err().cloned(), result)
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_replication_find() -> ReplicationSettings {
+    with_state(|s| s.business_replication_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_replication_update(replication: ReplicationSettings) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_replication_find());
+
+    if old == replication {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set replication: {old:?} -> {replication:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_replication_update(replication);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 同步状态, 包括落后的变更数量
+#[ic_cdk::query(guard = "has_business_query")]
+fn business_replication_status() -> QueryReplication {
+    with_state(|s| s.business_replication_status())
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_subscriptions_find() -> Vec<Subscription> {
+    with_state(|s| s.business_subscriptions_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_subscriptions_update(subscriptions: Vec<Subscription>) -> Result<(), String> {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_subscriptions_find());
+
+    if old == subscriptions {
+        return Ok(());
+    }
+
+    let caller = caller();
+    let arg_content = format!("set subscriptions: {old:?} -> {subscriptions:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            let result = s.business_subscriptions_update(subscriptions);
+            (result.as_ref().err().cloned(), result)
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_audit_find() -> AuditSettings {
+    with_state(|s| s.business_audit_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_audit_update(audit: AuditSettings) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_audit_find());
+
+    if old == audit {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!("set audit: {old:?} -> {audit:?}",); // * 记录参数内容
+
+    with_mut_state(
+        |s| {
+            s.business_audit_update(audit);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
+
+// 最近一次完整的审计结果
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_audit_report() -> Option<AuditReport> {
+    with_state(|s| s.business_audit_report())
+}
+
+// 查询
+#[ic_cdk::query(guard = "has_business_settings")]
+fn business_metrics_find() -> MetricsSettings {
+    with_state(|s| s.business_metrics_find())
+}
+#[ic_cdk::update(guard = "has_business_settings")]
+fn business_metrics_update(metrics: MetricsSettings) {
+    let _guard = call_once_guard(); // post 接口应该拦截
+
+    let old = with_state(|s| s.business_metrics_find());
+
+    if old == metrics {
+        return;
+    }
+
+    let caller = caller();
+    let arg_content = format!(
+        "set metrics: {:?} -> {:?}",
+        old.redacted(),
+        metrics.redacted()
+    ); // * 记录参数内容, 不记录令牌
+
+    with_mut_state(
+        |s| {
+            s.business_metrics_update(metrics);
+            (None, ())
+        },
+        caller,
+        RecordTopics::Settings.topic(),
+        arg_content,
+    )
+}
@@ -1,40 +1,335 @@
 use std::{borrow::Cow, collections::HashMap};
 
-use crate::stable::{Business, State};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+
+use crate::stable::{AssetFile, Business, ExploreSettings, State};
 
 pub const HTML: &str = include_str!("../web/index.html");
 pub const CSS: &str = include_str!("../web/index.css");
 
-pub fn explore<'a>(headers: &mut HashMap<&'a str, Cow<'a, str>>, state: &State) -> Vec<u8> {
+// 浏览页面获取数据的默认路径
+const EXPLORE_API_PATH: &str = "/__explore/api";
+
+// 分页大小
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+// 浏览页面获取数据的路径, 跟随浏览页面的独立路径, 如 /__explore/ -> /__explore/api
+pub fn explore_api_path(settings: &ExploreSettings) -> Cow<'static, str> {
+    match &settings.path {
+        Some(path) => format!("{}/api", path.trim_end_matches('/')).into(),
+        None => EXPLORE_API_PATH.into(),
+    }
+}
+
+// 页面本身不包含数据, 数据通过 explore_api_path 分页获取
+pub fn explore<'a>(
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+    settings: &ExploreSettings,
+) -> Vec<u8> {
     headers.insert("Content-Type", "text/html".into());
 
-    let files = state.business_files();
-    let mut json = String::from("");
-    json.push('[');
-    json.push_str(
-        &files
+    HTML.replace("/* CSS */", CSS).replace(
+        "/* EXPLORE_API_PATH */",
+        &script_string(&explore_api_path(settings)),
+    )[..]
+        .into()
+}
+
+// 放进脚本中的字符串, 需要 JSON 转义, 并且不能提前结束 script 标签
+fn script_string(text: &str) -> String {
+    serde_json::to_string(text)
+        .unwrap_or_else(|_| "\"\"".into())
+        .replace('<', "\\u003c")
+}
+
+#[derive(Serialize)]
+struct ExploreHeader<'a> {
+    key: &'a str,
+    value: &'a str,
+}
+
+#[derive(Serialize)]
+struct ExploreItem<'a> {
+    r#type: &'static str, // file 或者 dir
+    path: &'a str,
+    name: &'a str,
+    size: u64,
+    headers: Vec<ExploreHeader<'a>>,
+    created: i128,  // 毫秒
+    modified: i128, // 毫秒
+    hash: String,
+    files: u64, // 目录下的文件数量
+}
+
+#[derive(Serialize)]
+struct ExploreData<'a> {
+    dir: &'a str,
+    search: &'a str,
+    size: u64, // 目录总大小
+    total: usize,
+    page: usize,
+    page_size: usize,
+    items: Vec<ExploreItem<'a>>,
+}
+
+// 浏览页面的数据
+// ?dir=/docs/&search=&sort=name&order=asc&page=1&page_size=100
+pub fn explore_api<'a>(
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+    state: &State,
+    query: Option<&str>,
+    settings: &ExploreSettings,
+) -> Vec<u8> {
+    headers.insert("Content-Type", "application/json".into());
+    headers.insert("Cache-Control", "no-store".into());
+
+    let params = parse_query(query.unwrap_or_default());
+    let param = |name: &str| params.get(name).map(|v| v.as_str()).unwrap_or_default();
+    let mut dir = param("dir").to_string();
+    if !dir.starts_with('/') {
+        dir.insert(0, '/');
+    }
+    if !dir.ends_with('/') {
+        dir.push('/');
+    }
+    let search = param("search").trim().to_lowercase();
+    let page = param("page").parse::<usize>().unwrap_or(1).max(1);
+    let page_size = param("page_size")
+        .parse::<usize>()
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let mut files = state.business_assets_files(); // 只取引用, 不复制文件信息
+    files.retain(|file| settings.is_public(&file.path)); // 不显示私有路径
+
+    // 1. 当前目录下的文件和子目录, 搜索时是所有匹配的文件
+    let (size, mut items) = list_dir(&files, &dir, &search);
+
+    // 2. 排序, 目录在前
+    let sort = param("sort");
+    let mime = |item: &ExploreItem| {
+        item.headers
+            .iter()
+            .find(|h| h.key.eq_ignore_ascii_case("content-type"))
+            .map(|h| h.value.to_string())
+            .unwrap_or_default()
+    };
+    items.sort_by(|a, b| {
+        let ordering = match sort {
+            "mime" => mime(a).cmp(&mime(b)),
+            "size" => a.size.cmp(&b.size),
+            "created" => a.created.cmp(&b.created),
+            "modified" => a.modified.cmp(&b.modified),
+            _ => a.name.cmp(b.name),
+        };
+        let ordering = ordering.then_with(|| a.path.cmp(b.path));
+        let ordering = if param("order") == "desc" {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        a.r#type.cmp(b.r#type).then(ordering) // dir 在 file 前面
+    });
+
+    // 3. 分页
+    let total = items.len();
+    let items: Vec<ExploreItem> = items
+        .into_iter()
+        .skip((page - 1).saturating_mul(page_size))
+        .take(page_size)
+        .collect();
+
+    let data = ExploreData {
+        dir: &dir,
+        search: &search,
+        size,
+        total,
+        page,
+        page_size,
+        items,
+    };
+    serde_json::to_vec(&data).unwrap_or_default()
+}
+
+// 目录下的文件和子目录, 有搜索内容时是目录下所有匹配的文件
+fn list_dir<'a>(files: &[&'a AssetFile], dir: &str, search: &str) -> (u64, Vec<ExploreItem<'a>>) {
+    let mut size = 0;
+    let mut dirs: HashMap<&str, ExploreItem> = HashMap::new();
+    let mut items: Vec<ExploreItem> = vec![];
+    for file in files.iter().copied() {
+        let rest = match file.path.strip_prefix(dir) {
+            Some(rest) => rest,
+            None => continue,
+        };
+        size += file.size;
+        if !search.is_empty() {
+            if file.path.to_lowercase().contains(search) {
+                items.push(file_item(file, &file.path));
+            }
+            continue;
+        }
+        match rest.find('/') {
+            Some(index) => {
+                let path = &file.path[..dir.len() + index + 1];
+                let item = dirs.entry(path).or_insert_with(|| ExploreItem {
+                    r#type: "dir",
+                    path,
+                    name: &rest[..index + 1],
+                    size: 0,
+                    headers: vec![],
+                    created: millis(file.created.into_inner()),
+                    modified: millis(file.modified.into_inner()),
+                    hash: String::new(),
+                    files: 0,
+                });
+                item.size += file.size;
+                item.files += 1;
+                item.created = item.created.min(millis(file.created.into_inner()));
+                item.modified = item.modified.max(millis(file.modified.into_inner()));
+            }
+            None => items.push(file_item(file, rest)),
+        }
+    }
+
+    let items = dirs.into_values().chain(items).collect();
+    (size, items)
+}
+
+#[inline]
+fn millis(nanos: i128) -> i128 {
+    nanos / 1000000
+}
+
+#[inline]
+fn file_item<'a>(file: &'a AssetFile, name: &'a str) -> ExploreItem<'a> {
+    ExploreItem {
+        r#type: "file",
+        path: &file.path,
+        name,
+        size: file.size,
+        headers: file
+            .headers
             .iter()
-            .map(|file| {
-                format!(
-                    "{{path:\"{}\",size:{},headers:[{}],created:{},modified:{},hash:\"{}\"}}",
-                    file.path,
-                    file.size,
-                    file.headers
-                        .iter()
-                        .map(|(key, value)| format!("{{key:\"{}\",value:\"{}\"}}", key, value))
-                        .collect::<Vec<String>>()
-                        .join(","),
-                    file.created.into_inner() / 1000000,
-                    file.modified.into_inner() / 1000000,
-                    file.hash
-                )
-            })
-            .collect::<Vec<String>>()
-            .join(","),
+            .map(|(key, value)| ExploreHeader { key, value })
+            .collect(),
+        created: millis(file.created.into_inner()),
+        modified: millis(file.modified.into_inner()),
+        hash: file.hash.hex(),
+        files: 1,
+    }
+}
+
+// 解析请求参数, 先分割再解码
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| {
+                let s = s.replace('+', " ");
+                percent_decode_str(&s).decode_utf8_lossy().to_string()
+            };
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+// 链接中需要转义的字符
+const PATH_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`');
+
+// html 转义
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Serialize)]
+struct AutoindexData<'a> {
+    dir: &'a str,
+    items: Vec<ExploreItem<'a>>,
+}
+
+// 目录列表页面, 请求 application/json 时返回 json, 目录不存在时返回 None
+pub fn autoindex<'a>(
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+    state: &State,
+    dir: &str,
+    json: bool,
+    settings: &ExploreSettings,
+) -> Option<Vec<u8>> {
+    let mut files = state.business_assets_files();
+    files.retain(|file| settings.is_public(&file.path)); // 不显示私有路径
+
+    let (_, mut items) = list_dir(&files, dir, "");
+    if items.is_empty() && dir != "/" {
+        return None;
+    }
+    items.sort_by(|a, b| a.r#type.cmp(b.r#type).then_with(|| a.name.cmp(b.name)));
+
+    headers.insert("Vary", "Accept".into());
+    if json {
+        headers.insert("Content-Type", "application/json".into());
+        return serde_json::to_vec(&AutoindexData { dir, items }).ok();
+    }
+
+    headers.insert("Content-Type", "text/html; charset=utf-8".into());
+
+    let title = escape_html(&format!("Index of {dir}"));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n<hr>\n<pre>\n"
     );
-    json.push(']');
+    if dir != "/" {
+        html.push_str("<a href=\"../\">../</a>\n");
+    }
+    for item in items.iter() {
+        let href = utf8_percent_encode(item.name, PATH_ENCODE_SET).to_string();
+        let name = escape_html(item.name);
+        let padding = " ".repeat(50usize.saturating_sub(item.name.chars().count()));
+        html.push_str(&format!(
+            "<a href=\"./{href}\">{name}</a>{padding} {}  {:>15}\n",
+            crate::http::http_date(item.modified * 1000000),
+            if item.r#type == "dir" {
+                "-".to_string()
+            } else {
+                item.size.to_string()
+            },
+        ));
+    }
+    html.push_str("</pre>\n<hr>\n</body>\n</html>\n");
+    Some(html.into_bytes())
+}
 
-    HTML.replace("/* CSS */", CSS)
-        .replace("const _files = [];", &format!("const _files = {};", json))[..]
-        .into()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_string() {
+        assert_eq!(script_string("/__explore/api"), r#""/__explore/api""#);
+        assert_eq!(
+            script_string(r#"/a";alert(1);"</script>/api"#),
+            r#""/a\";alert(1);\"\u003c/script>/api""#
+        );
+    }
 }
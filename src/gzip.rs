@@ -0,0 +1,126 @@
+use miniz_oxide::deflate::core::{
+    compress_to_output, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
+
+// https://www.rfc-editor.org/rfc/rfc1952
+// 固定的 gzip 头: ID1 ID2 CM(deflate) FLG MTIME(4) XFL OS(unknown)
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff];
+
+// 默认压缩等级
+const GZIP_LEVEL: i32 = 6;
+
+/// 可以分多次输入数据的 gzip 压缩
+pub struct GzipEncoder {
+    compressor: Box<CompressorOxide>,
+    crc: crc32fast::Hasher,
+    size: u64,       // 已经输入的长度
+    output: Vec<u8>, // 已经压缩的数据
+}
+
+impl Default for GzipEncoder {
+    fn default() -> Self {
+        // window_bits 为负数表示输出原始的 deflate 数据, 由 gzip 自己包装
+        let flags = create_comp_flags_from_zip_params(GZIP_LEVEL, -15, 0);
+        Self {
+            compressor: Box::new(CompressorOxide::new(flags)),
+            crc: crc32fast::Hasher::new(),
+            size: 0,
+            output: GZIP_HEADER.to_vec(),
+        }
+    }
+}
+
+impl GzipEncoder {
+    /// 输入一段数据
+    pub fn update(&mut self, data: &[u8]) -> Result<(), String> {
+        self.crc.update(data);
+        self.size += data.len() as u64;
+        self.compress(data, TDEFLFlush::None)
+    }
+
+    /// 结束压缩, 返回完整的 gzip 数据
+    pub fn finish(mut self) -> Result<Vec<u8>, String> {
+        self.compress(&[], TDEFLFlush::Finish)?;
+        let crc = self.crc.clone().finalize();
+        self.output.extend_from_slice(&crc.to_le_bytes());
+        self.output
+            .extend_from_slice(&(self.size as u32).to_le_bytes()); // 原始长度取模 2^32
+        Ok(self.output)
+    }
+
+    fn compress(&mut self, mut data: &[u8], flush: TDEFLFlush) -> Result<(), String> {
+        let output = &mut self.output;
+        loop {
+            let (status, bytes_in) = compress_to_output(&mut self.compressor, data, flush, |out| {
+                output.extend_from_slice(out);
+                true
+            });
+            data = &data[bytes_in.min(data.len())..];
+            match status {
+                TDEFLStatus::Okay if !data.is_empty() => continue, // 还有数据没有处理
+                TDEFLStatus::Okay if flush == TDEFLFlush::Finish => continue, // 还没有结束
+                TDEFLStatus::Okay | TDEFLStatus::Done => return Ok(()),
+                status => return Err(format!("gzip compress failed: {:?}", status)), // 压缩出错
+            }
+        }
+    }
+}
+
+/// 值得压缩的内容类型
+pub fn compressible(content_type: &str) -> bool {
+    let content_type = content_type.to_lowercase();
+    content_type.starts_with("text/")
+        || content_type.contains("javascript")
+        || content_type.contains("json")
+        || content_type.contains("svg")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gzip(chunks: &[&[u8]]) -> Result<Vec<u8>, String> {
+        let mut encoder = GzipEncoder::default();
+        for chunk in chunks {
+            encoder.update(chunk)?;
+        }
+        encoder.finish()
+    }
+
+    fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+        assert_eq!(&data[..10], &GZIP_HEADER);
+        let (body, trailer) = data[10..].split_at(data.len() - 18);
+        let output =
+            miniz_oxide::inflate::decompress_to_vec(body).map_err(|e| format!("{:?}", e))?;
+        assert_eq!(&trailer[..4], &crc32fast::hash(&output).to_le_bytes());
+        assert_eq!(&trailer[4..], &(output.len() as u32).to_le_bytes());
+        Ok(output)
+    }
+
+    #[test]
+    fn test_gzip_round_trip() -> Result<(), String> {
+        let text = "hello gzip, ".repeat(10000);
+        let data = text.as_bytes();
+        let whole = gzip(&[data])?;
+        assert!(whole.len() < data.len());
+        assert_eq!(gunzip(&whole)?, data);
+
+        // 分段输入的结果一致
+        let (a, b) = data.split_at(12345);
+        assert_eq!(gunzip(&gzip(&[a, b])?)?, data);
+
+        // 空数据
+        assert_eq!(gunzip(&gzip(&[])?)?, b"");
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressible() {
+        assert!(compressible("text/html; charset=utf-8"));
+        assert!(compressible("application/javascript"));
+        assert!(compressible("application/json"));
+        assert!(compressible("image/svg+xml"));
+        assert!(!compressible("image/png"));
+        assert!(!compressible("application/octet-stream"));
+    }
+}
@@ -5,7 +5,9 @@ use regex::Regex;
 
 use ic_canister_kit::http::MAX_RESPONSE_LENGTH;
 
-use crate::explore::explore;
+use crate::explore::{autoindex, explore, explore_api, explore_api_path};
+use crate::metrics::metrics;
+use crate::mime::{infer_content_type, MAGIC_HEAD_SIZE};
 use crate::stable::State;
 use crate::types::*;
 
@@ -24,6 +26,8 @@ fn http_request(request: CustomHttpRequest) -> CustomHttpResponse {
 
 #[inline]
 fn inner_http_request(state: &State, req: CustomHttpRequest) -> CustomHttpResponse {
+    let method = req.method.to_uppercase();
+    let mut query = req.url.split_once('?').map(|(_, query)| query); // 原始的请求参数
     let mut split_url = req.url.split('?');
     let request_headers = req.headers;
 
@@ -43,36 +47,140 @@ fn inner_http_request(state: &State, req: CustomHttpRequest) -> CustomHttpRespon
 
     let mut code = 200; // 响应码默认是 200
     let mut headers: HashMap<&str, Cow<str>> = HashMap::new();
-    let body: Vec<u8>;
+    let mut body: Vec<u8>;
     let mut streaming_strategy: Option<StreamingStrategy> = None;
+    let head = method == "HEAD"; // 只需要响应头
 
-    if path == "/" {
-        body = explore(&mut headers, state); // 主页内容
+    let routing = state.business_routing_find();
+    let explore_settings = state.business_explore_find();
+    let cors = state.business_assets_cors_rule(&path); // 跨域设置
+    let origin = find_header(&request_headers, "origin");
+    let route = state.business_assets_route(&path); // 重定向和重写规则
+    let (path, params) = match route {
+        // 内部重写, 目标中的请求参数替换原来的请求参数
+        Some(RouteAction::Rewrite(ref target)) => match target.split_once('?') {
+            Some((target, target_query)) => {
+                query = Some(target_query);
+                let target_params = percent_decode_str(target_query)
+                    .decode_utf8()
+                    .unwrap_or(Cow::Borrowed(target_query));
+                (Cow::Owned(target.to_string()), target_params)
+            }
+            None => (Cow::Owned(target.clone()), params),
+        },
+        _ => (path, params),
+    };
+    if method == "OPTIONS" {
+        body = options(cors, origin, &request_headers, &mut code, &mut headers);
+    // 预检请求
+    } else if method != "GET" && method != "HEAD" {
+        body = method_not_allowed(&mut code, &mut headers);
+    } else if let Some(settings) = Some(state.business_metrics_find())
+        .filter(|settings| settings.enabled && path == settings.path)
+    {
+        // 监控指标, 维护中也可以访问
+        body = if metrics_permitted(&settings, &request_headers) {
+            metrics(&mut headers, state)
+        } else {
+            error_page(state, &path, &request_headers, 403, &mut code, &mut headers)
+        };
+    } else if let Some(maintenance) = maintaining(state, &path) {
+        body = maintenance_page(
+            state,
+            &maintenance,
+            &path,
+            &request_headers,
+            &mut code,
+            &mut headers,
+        ); // 维护中
+    } else if let Some(RouteAction::Redirect { status, location }) = route {
+        body = redirect(status, location, query, &mut code, &mut headers); // 重定向
+    } else if let Some(hash) = parse_hash_path(&path) {
+        // ! 尚未校验的数据可能不是声称的内容, 不能作为永久缓存的内容响应
+        let asset = state
+            .business_assets_get(&hash)
+            .filter(|_| state.business_assets_verified(&hash));
+        body = match asset {
+            Some(asset) => {
+                let (_body, _streaming_strategy) =
+                    toast_hash(state, &path, &hash, asset, head, &mut code, &mut headers); // 按 hash 访问数据
+                streaming_strategy = _streaming_strategy;
+                _body
+            }
+            None => error_page(state, &path, &request_headers, 404, &mut code, &mut headers),
+        };
+    } else if let Some(manifest) = (path == state.business_manifest_find().path)
+        .then(|| state.business_assets_manifest())
+        .flatten()
+    {
+        headers.insert("Content-Type", "application/json".into());
+        headers.insert("Cache-Control", "no-cache".into());
+        headers.insert("ETag", hash_data(&manifest).hex().into());
+        body = manifest.into_owned(); // 资源清单
+    } else if path == explore_api_path(&explore_settings) && explore_settings.enabled() {
+        body = if explore_permitted(state) {
+            explore_api(&mut headers, state, query, &explore_settings) // 浏览页面的数据
+        } else {
+            error_page(state, &path, &request_headers, 403, &mut code, &mut headers)
+        };
+    } else if explore_settings.is_explorer(&path) {
+        body = if explore_permitted(state) {
+            explore(&mut headers, &explore_settings) // 浏览页面
+        } else {
+            error_page(state, &path, &request_headers, 403, &mut code, &mut headers)
+        };
+    } else if path == "/" && explore_settings.root == RootPage::Nothing {
+        body = error_page(state, &path, &request_headers, 404, &mut code, &mut headers);
+    } else if autoindexed(state, &routing, &path) {
+        let json = find_header(&request_headers, "accept")
+            .map(|accept| accept.to_lowercase().contains("application/json"))
+            .unwrap_or(false);
+        body = match autoindex(&mut headers, state, &path, json, &explore_settings) {
+            Some(body) => body, // 目录列表
+            None => error_page(state, &path, &request_headers, 404, &mut code, &mut headers),
+        };
     } else {
         // 根据路径找文件
-        let file = state.business_assets_get_file(path.as_ref());
+        let file = find_file(state, &routing, &path, &request_headers);
         if let Some(file) = file {
-            let asset = state.business_assets_get(&file.hash);
+            let variant = select_variant(&request_headers, file); // 根据请求选择编码
+            let asset = state.business_assets_get(variant.hash);
             if let Some(asset) = asset {
                 let (_body, _streaming_strategy): (Vec<u8>, Option<StreamingStrategy>) = toast(
-                    &path,
+                    &file.path, // 可能是路由后的文件
                     &params,
                     &request_headers,
                     file,
+                    &variant,
                     asset,
+                    head,
                     &mut code,
                     &mut headers,
                 ); // 有对应的文件
+                apply_header_rules(state, &file.path, &mut headers); // 路径规则的响应头
+                infer_headers(state, file, &mut headers); // 没有内容类型的需要推断
                 body = _body;
                 streaming_strategy = _streaming_strategy;
             } else {
-                body = not_found(&mut code, &mut headers);
+                body = error_page(state, &path, &request_headers, 404, &mut code, &mut headers);
             }
         } else {
-            body = not_found(&mut code, &mut headers);
+            body = error_page(state, &path, &request_headers, 404, &mut code, &mut headers);
+        }
+    }
+
+    if method != "OPTIONS" {
+        if let Some(allow_origin) = cors.zip(origin).and_then(|(c, o)| c.allow_origin(o)) {
+            headers.insert("Access-Control-Allow-Origin", allow_origin.into());
+            headers.insert("Vary", vary(headers.get("Vary"), "Origin").into());
         }
     }
 
+    if head {
+        body = vec![]; // 只需要响应头
+        streaming_strategy = None;
+    }
+
     CustomHttpResponse {
         status_code: code,
         headers: headers
@@ -85,37 +193,390 @@ fn inner_http_request(state: &State, req: CustomHttpRequest) -> CustomHttpRespon
     }
 }
 
+// 响应内容的 hash
+#[inline]
+fn hash_data(data: &[u8]) -> HashDigest {
+    use sha2::Digest;
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(data);
+    HashDigest(hasher.finalize().into())
+}
+
+// 按 hash 访问数据的路径 /__hash/<hex>.<ext>
+const HASH_PATH_PREFIX: &str = "/__hash/";
+
+#[inline]
+fn parse_hash_path(path: &str) -> Option<HashDigest> {
+    let name = path.strip_prefix(HASH_PATH_PREFIX)?;
+    let hex = name.split_once('.').map(|(hex, _)| hex).unwrap_or(name);
+    HashDigest::from_hex(hex)
+}
+
+// 按 hash 访问的数据不会变化, 可以永久缓存
+#[inline]
+fn toast_hash<'a>(
+    state: &'a State,
+    path: &str,
+    hash: &HashDigest,
+    asset: &AssetData,
+    head: bool,
+    code: &mut u16,
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+) -> (Vec<u8>, Option<StreamingStrategy>) {
+    *code = 200;
+    let size = asset.size();
+    let magic = if 0 < size {
+        asset.slice(hash, size as u64, 0, size.min(MAGIC_HEAD_SIZE))
+    } else {
+        Cow::Borrowed(&[][..])
+    };
+
+    // 内容类型: 指定的拓展名 > 引用该数据的文件 > 数据特征
+    let by_extension = path
+        .rsplit('/')
+        .next()
+        .filter(|name| name.contains('.'))
+        .and_then(|_| infer_content_type(path, &magic))
+        .map(|(content_type, _)| content_type);
+    let by_file = state
+        .business_assets_get_file_by_hash(hash)
+        .and_then(|file| {
+            file.headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        })
+        .map(|(_, value)| value.as_str());
+    let by_magic = || infer_content_type("", &magic).map(|(content_type, _)| content_type);
+    if let Some(content_type) = by_extension.or(by_file).or_else(by_magic) {
+        headers.insert("Content-Type", content_type.into());
+    }
+    headers.insert("ETag", hash.hex().into());
+    headers.insert(
+        "Cache-Control",
+        "public, max-age=31536000, immutable".into(),
+    );
+
+    if head {
+        headers.insert("Content-Length", size.to_string().into()); // HEAD 请求也要给出完整的长度
+        return (vec![], None);
+    }
+    if size == 0 {
+        return (vec![], None);
+    }
+    let mut streaming_end = size;
+    let mut streaming_strategy = None;
+    if MAX_RESPONSE_LENGTH < size {
+        streaming_end = MAX_RESPONSE_LENGTH;
+        streaming_strategy = Some(to_streaming_strategy(
+            path.to_string(),
+            None,
+            streaming_end as u64,
+            size as u64,
+        ));
+    } else {
+        headers.insert("Content-Length", size.to_string().into()); // ! 流式响应设置了长度会出错
+    }
+    (
+        asset.slice(hash, size as u64, 0, streaming_end).to_vec(),
+        streaming_strategy,
+    )
+}
+
+// 浏览页面需要查询权限, 通过网关访问时调用者是匿名身份
+#[inline]
+fn explore_permitted(state: &State) -> bool {
+    state
+        .parse_permission(ACTION_BUSINESS_QUERY)
+        .map(|permission| state.permission_has(&ic_cdk::caller(), &permission))
+        .unwrap_or(false)
+}
+
+// 监控指标需要令牌或者调用者在允许列表中
+fn metrics_permitted(
+    settings: &MetricsSettings,
+    request_headers: &HashMap<String, String>,
+) -> bool {
+    let token = find_header(request_headers, "authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim());
+    settings.permitted(&ic_cdk::caller(), token)
+}
+
+// 支持的请求方法
+const ALLOW_METHODS: &str = "GET, HEAD, OPTIONS";
+
+// 查找请求头
+#[inline]
+fn find_header<'a>(request_headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    request_headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+// 追加 Vary 的内容
+#[inline]
+fn vary(exist: Option<&Cow<'_, str>>, value: &str) -> String {
+    match exist {
+        Some(exist) if !exist.is_empty() => format!("{exist}, {value}"),
+        _ => value.to_string(),
+    }
+}
+
+// OPTIONS 请求, 有跨域设置就响应预检
+#[inline]
+fn options<'a>(
+    cors: Option<&'a CorsRule>,
+    origin: Option<&str>,
+    request_headers: &HashMap<String, String>,
+    code: &mut u16,
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+) -> Vec<u8> {
+    *code = 204;
+    headers.insert("Allow", ALLOW_METHODS.into());
+
+    let preflight = find_header(request_headers, "access-control-request-method").is_some();
+    if let (true, Some(cors), Some(origin)) = (preflight, cors, origin) {
+        if let Some(allow_origin) = cors.allow_origin(origin) {
+            headers.insert("Access-Control-Allow-Origin", allow_origin.into());
+            headers.insert(
+                "Access-Control-Allow-Methods",
+                if cors.methods.is_empty() {
+                    ALLOW_METHODS.into()
+                } else {
+                    cors.methods.join(", ").into()
+                },
+            );
+            if !cors.headers.is_empty() {
+                headers.insert(
+                    "Access-Control-Allow-Headers",
+                    cors.headers.join(", ").into(),
+                );
+            }
+            if let Some(max_age) = cors.max_age {
+                headers.insert("Access-Control-Max-Age", max_age.to_string().into());
+            }
+            headers.insert("Vary", "Origin".into());
+        }
+    }
+
+    vec![]
+}
+
+// 不支持的请求方法
+#[inline]
+fn method_not_allowed<'a>(code: &mut u16, headers: &mut HashMap<&'a str, Cow<'a, str>>) -> Vec<u8> {
+    *code = 405;
+    headers.insert("Allow", ALLOW_METHODS.into());
+    headers.insert("Content-Type", "text/plain".into());
+
+    b"Method Not Allowed"[..].into()
+}
+
+// 重定向, 目标没有请求参数时保留原始的请求参数
+#[inline]
+fn redirect<'a>(
+    status: u16,
+    mut location: String,
+    query: Option<&str>,
+    code: &mut u16,
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+) -> Vec<u8> {
+    *code = status;
+
+    if let Some(query) = query.filter(|q| !q.is_empty()) {
+        if !location.contains('?') {
+            location.push('?');
+            location.push_str(query);
+        }
+    }
+    headers.insert("Location", location.into());
+
+    vec![]
+}
+
+// 根据路由设置找到对应的文件
+#[inline]
+fn find_file<'a>(
+    state: &'a State,
+    routing: &RoutingSettings,
+    path: &str,
+    request_headers: &HashMap<String, String>,
+) -> Option<&'a AssetFile> {
+    // 1. 完全匹配
+    if let Some(file) = state.business_assets_get_file(path) {
+        return Some(file);
+    }
+
+    // 2. 目录索引文件
+    if path.ends_with('/') {
+        let file = routing
+            .index_files
+            .iter()
+            .find_map(|name| state.business_assets_get_file(&format!("{path}{name}")));
+        if file.is_some() {
+            return file;
+        }
+    }
+
+    // 3. 未知路径回退
+    let fallback = routing.fallback.as_ref()?;
+    if routing.fallback_html_only && !accept_html(request_headers) {
+        return None; // 资源请求不需要回退
+    }
+    state.business_assets_get_file(fallback)
+}
+
+// 目录没有索引文件, 并且开启了目录列表
+#[inline]
+fn autoindexed(state: &State, routing: &RoutingSettings, path: &str) -> bool {
+    path.ends_with('/')
+        && routing.autoindex_enabled(path)
+        && state.business_assets_get_file(path).is_none()
+        && routing.index_files.iter().all(|name| {
+            state
+                .business_assets_get_file(&format!("{path}{name}"))
+                .is_none()
+        })
+}
+
+// 是否接受 html 响应
+#[inline]
+fn accept_html(request_headers: &HashMap<String, String>) -> bool {
+    request_headers
+        .iter()
+        .find(|(key, _)| key.to_lowercase() == "accept")
+        .map(|(_, v)| v.to_lowercase().contains("text/html"))
+        .unwrap_or(false)
+}
+
+// 本次响应选中的数据, 可能是文件本身, 也可能是文件的某种编码
+struct Variant<'a> {
+    encoding: Option<&'a str>,
+    hash: &'a HashDigest,
+    size: u64,
+}
+
+// 服务端偏好的编码顺序
+const ENCODING_PREFERENCES: [&str; 2] = ["br", "gzip"];
+
+// 根据 Accept-Encoding 选择编码
+#[inline]
+fn select_variant<'a>(
+    request_headers: &HashMap<String, String>,
+    file: &'a AssetFile,
+) -> Variant<'a> {
+    let identity = Variant {
+        encoding: None,
+        hash: &file.hash,
+        size: file.size,
+    };
+    if file.encodings.is_empty() {
+        return identity;
+    }
+    let accept_encoding = match request_headers
+        .iter()
+        .find(|(key, _)| key.to_lowercase() == "accept-encoding")
+        .map(|(_, v)| v.to_lowercase())
+    {
+        Some(accept_encoding) => accept_encoding,
+        None => return identity,
+    };
+
+    // https://developer.mozilla.org/zh-CN/docs/Web/HTTP/Headers/Accept-Encoding
+    // Accept-Encoding: br;q=1.0, gzip;q=0.8, *;q=0.1
+    let accepted: Vec<(&str, f32)> = accept_encoding
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let name = parts.next()?.trim();
+            let q = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!name.is_empty()).then_some((name, q))
+        })
+        .collect();
+    let quality = |encoding: &str| {
+        accepted
+            .iter()
+            .find(|(name, _)| *name == encoding)
+            .or_else(|| accepted.iter().find(|(name, _)| *name == "*"))
+            .map(|(_, q)| *q)
+            .unwrap_or(0.0)
+    };
+    let preference = |encoding: &str| {
+        ENCODING_PREFERENCES
+            .iter()
+            .position(|e| *e == encoding)
+            .unwrap_or(ENCODING_PREFERENCES.len())
+    };
+
+    file.encodings
+        .iter()
+        .map(|(encoding, e)| (encoding, e, quality(encoding)))
+        .filter(|(_, _, q)| 0.0 < *q)
+        .min_by(|(a, ae, aq), (b, be, bq)| {
+            bq.total_cmp(aq) // q 值大的优先
+                .then_with(|| preference(a).cmp(&preference(b)))
+                .then_with(|| ae.size.cmp(&be.size))
+        })
+        .map(|(encoding, e, _)| Variant {
+            encoding: Some(encoding),
+            hash: &e.hash,
+            size: e.size,
+        })
+        .unwrap_or(identity)
+}
+
+#[allow(clippy::too_many_arguments)]
 #[inline]
 fn toast<'a>(
     path: &str,
     params: &str,
     request_headers: &HashMap<String, String>,
     file: &'a AssetFile,
+    variant: &Variant<'a>,
     asset: &AssetData,
+    head: bool,
     code: &mut u16,
     headers: &mut HashMap<&'a str, Cow<'a, str>>,
 ) -> (Vec<u8>, Option<StreamingStrategy>) {
     // 1. 设置 header
-    let (offset, size, streaming_strategy) =
-        set_headers(path, params, request_headers, file, code, headers);
+    let (offset, size, streaming_strategy) = set_headers(
+        path,
+        params,
+        request_headers,
+        file,
+        variant,
+        head,
+        code,
+        headers,
+    );
+    if head {
+        return (vec![], None); // 只需要响应头, 不读取内容
+    }
 
     // 2. 返回指定的内容
     (
-        (asset.slice(&file.hash, file.size, offset, size)).to_vec(),
+        (asset.slice(variant.hash, variant.size, offset, size)).to_vec(),
         streaming_strategy,
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 #[inline]
 fn set_headers<'a>(
     path: &str,
     params: &str,
     request_headers: &HashMap<String, String>,
     file: &'a AssetFile,
+    variant: &Variant<'a>,
+    head: bool,
     code: &mut u16,
     headers: &mut HashMap<&'a str, Cow<'a, str>>,
 ) -> (usize, usize, Option<StreamingStrategy>) {
-    let size = file.size as usize;
+    let size = variant.size as usize;
 
     // let mut gzip = false;
     // let mut content_type = "";
@@ -151,9 +612,14 @@ fn set_headers<'a>(
     // use chrono::{TimeZone, Utc};
     // let modified = Utc.timestamp_nanos(file.modified as i64);
     // headers.insert("Last-Modified", modified.to_rfc2822().into());
+    headers.insert(
+        "Last-Modified",
+        http_date(file.modified.into_inner()).into(),
+    ); // 修改时间
 
     // 额外增加的请求头
-    headers.insert("ETag", file.hash.hex().into()); // 缓存标识
+    headers.insert("ETag", variant.hash.hex().into()); // 缓存标识
+    headers.insert("Accept-Ranges", "bytes".into()); // 支持范围请求
 
     // 访问控制
     // headers.insert("Access-Control-Allow-Origin", "*".into());
@@ -205,23 +671,31 @@ fn set_headers<'a>(
         headers.insert(name, value.into());
     }
 
+    // 编码协商
+    if !file.encodings.is_empty() {
+        headers.insert("Vary", "Accept-Encoding".into()); // 同一路径有多种编码
+    }
+    if let Some(encoding) = variant.encoding {
+        headers.retain(|name, _| name.to_lowercase() != "content-encoding");
+        headers.insert("Content-Encoding", encoding.into());
+    }
+
     // ic_cdk::println!("---------- {} {} ----------", start, end);
     // 如果过长, 需要阶段显示
     let mut streaming_end = offset_end; // ! 末尾位置 不包含
     let mut streaming_strategy: Option<StreamingStrategy> = None;
-    if offset + MAX_RESPONSE_LENGTH < streaming_end {
+    if !head && offset + MAX_RESPONSE_LENGTH < streaming_end {
         // 响应的范围太大了, 缩短为最大长度, 此时应当开启流式响应
         streaming_end = offset + MAX_RESPONSE_LENGTH; // ! 末尾位置 不包含
         streaming_strategy = Some(to_streaming_strategy(
             path.to_string(),
+            variant.encoding,
             streaming_end as u64,
             offset_end as u64,
         ));
     }
 
     if ranged {
-        headers.insert("Accept-Ranges", "bytes".into()); // 支持范围请求
-
         // https://developer.mozilla.org/zh-CN/docs/Web/HTTP/Headers/Content-Range
         // Content-Range: bytes 0-499/10000
         headers.insert(
@@ -230,8 +704,10 @@ fn set_headers<'a>(
         );
     }
 
-    // ! 长度设置了会出错
-    // headers.insert("Content-Length", format!("{}", offset_end - offset).into()); // ? 这个应该是本次返回的长度
+    // 流式响应设置了长度会出错, HEAD 请求没有流式响应, 给出完整的长度
+    if streaming_strategy.is_none() {
+        headers.insert("Content-Length", (offset_end - offset).to_string().into());
+    }
 
     // 如果是视频可能需要返回其他的
     *code = 200;
@@ -242,44 +718,242 @@ fn set_headers<'a>(
     (offset, streaming_end - offset, streaming_strategy)
 }
 
-// 找不到对应的文件
+// 合并路径规则的响应头, 响应头名称不区分大小写
+#[inline]
+fn apply_header_rules<'a>(
+    state: &'a State,
+    path: &str,
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+) {
+    let rules = state.business_assets_header_rules(path);
+    // 不覆盖的规则倒序处理, 覆盖的规则顺序处理, 都是后面的规则优先
+    let (overwrite, keep): (Vec<_>, Vec<_>) = rules.into_iter().partition(|rule| rule.overwrite);
+    for rule in keep.into_iter().rev().chain(overwrite) {
+        for (name, value) in rule.headers.iter() {
+            let exists = headers.keys().any(|key| key.eq_ignore_ascii_case(name));
+            if exists && !rule.overwrite {
+                continue; // 已有的响应头优先
+            }
+            headers.retain(|key, _| !key.eq_ignore_ascii_case(name));
+            headers.insert(name, value.into());
+        }
+    }
+}
+
+// https://developer.mozilla.org/zh-CN/docs/Web/HTTP/Headers/Last-Modified
+// Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT
+pub fn http_date(nanos: i128) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let seconds = (nanos / 1_000_000_000).max(0);
+    let days = seconds / 86400;
+    let rest = seconds % 86400;
+    let weekday = (days + 4) % 7; // 1970-01-01 是星期四
+
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[weekday as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+// 之前上传的文件可能没有内容类型, 响应时推断
+#[inline]
+fn infer_headers<'a>(
+    state: &'a State,
+    file: &'a AssetFile,
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+) {
+    let exists = |headers: &HashMap<&str, Cow<str>>, name: &str| {
+        headers.keys().any(|key| key.eq_ignore_ascii_case(name))
+    };
+    if exists(headers, "content-type") {
+        return;
+    }
+    let head = state
+        .business_assets_get(&file.hash)
+        .filter(|_| 0 < file.size)
+        .map(|asset| {
+            let size = (file.size as usize).min(MAGIC_HEAD_SIZE);
+            asset.slice(&file.hash, file.size, 0, size)
+        })
+        .unwrap_or_default();
+    if let Some((content_type, encoding)) = infer_content_type(&file.path, &head) {
+        headers.insert("Content-Type", content_type.into());
+        if let Some(encoding) = encoding {
+            if !exists(headers, "content-encoding") {
+                headers.insert("Content-Encoding", encoding.into());
+            }
+        }
+    }
+}
+
+// 错误响应, 有设置错误页面就使用对应的文件内容, 响应码保持不变
 #[inline]
-fn not_found<'a>(code: &mut u16, headers: &mut HashMap<&'a str, Cow<'a, str>>) -> Vec<u8> {
-    *code = 404;
+fn error_page<'a>(
+    state: &'a State,
+    path: &str,
+    request_headers: &HashMap<String, String>,
+    status: u16,
+    code: &mut u16,
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+) -> Vec<u8> {
+    *code = status;
+
+    let page = state
+        .business_assets_get_error_page(path, status)
+        .and_then(|page| state.business_assets_get_file(&page.path));
+    if let Some(body) = page.and_then(|file| page_body(state, request_headers, file, headers)) {
+        return body;
+    }
 
     headers.insert("Content-Type", "text/plain".into());
 
-    b"Not found"[..].into()
+    match status {
+        403 => b"Forbidden"[..].into(),
+        404 => b"Not found"[..].into(),
+        503 => b"Service Unavailable"[..].into(),
+        _ => b"Error"[..].into(),
+    }
 }
 
+// 作为响应内容的页面文件, 不支持范围请求和流式响应
 #[inline]
-fn to_streaming_strategy(path: String, offset: u64, offset_end: u64) -> StreamingStrategy {
+fn page_body<'a>(
+    state: &'a State,
+    request_headers: &HashMap<String, String>,
+    file: &'a AssetFile,
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+) -> Option<Vec<u8>> {
+    let variant = select_variant(request_headers, file);
+    let asset = state.business_assets_get(variant.hash)?;
+    for (name, value) in file.headers.iter() {
+        headers.insert(name, value.into());
+    }
+    if !file.encodings.is_empty() {
+        headers.insert("Vary", "Accept-Encoding".into());
+    }
+    if let Some(encoding) = variant.encoding {
+        headers.retain(|name, _| name.to_lowercase() != "content-encoding");
+        headers.insert("Content-Encoding", encoding.into());
+    }
+    apply_header_rules(state, &file.path, headers);
+    infer_headers(state, file, headers);
+    let size = (variant.size as usize).min(MAX_RESPONSE_LENGTH);
+    if size == 0 {
+        return Some(vec![]);
+    }
+    Some(asset.slice(variant.hash, variant.size, 0, size).to_vec())
+}
+
+// 罐子维护中, 并且该路径不允许访问
+#[inline]
+fn maintaining(state: &State, path: &str) -> Option<MaintenanceSettings> {
+    if state.pause_is_running() {
+        return None;
+    }
+    let maintenance = state.business_maintenance_find();
+    if !maintenance.enabled
+        || maintenance
+            .allowed_prefixes
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+    {
+        return None;
+    }
+    Some(maintenance)
+}
+
+// 维护中的响应
+#[inline]
+fn maintenance_page<'a>(
+    state: &'a State,
+    maintenance: &MaintenanceSettings,
+    path: &str,
+    request_headers: &HashMap<String, String>,
+    code: &mut u16,
+    headers: &mut HashMap<&'a str, Cow<'a, str>>,
+) -> Vec<u8> {
+    let page = maintenance
+        .page
+        .as_ref()
+        .and_then(|page| state.business_assets_get_file(page));
+    let body = match page.and_then(|file| page_body(state, request_headers, file, headers)) {
+        Some(body) => {
+            *code = 503;
+            body
+        }
+        None => error_page(state, path, request_headers, 503, code, headers),
+    };
+
+    headers.retain(|name, _| name.to_lowercase() != "cache-control");
+    headers.insert("Cache-Control", "no-store".into()); // 维护页面不能缓存
+    headers.insert("Retry-After", maintenance.retry_after.to_string().into());
+
+    body
+}
+
+#[inline]
+fn to_streaming_strategy(
+    path: String,
+    encoding: Option<&str>,
+    offset: u64,
+    offset_end: u64,
+) -> StreamingStrategy {
     StreamingStrategy::Callback {
         callback: HttpRequestStreamingCallback::new(ic_cdk::id(), "http_streaming".into()),
-        token: to_streaming_token(path, offset, offset_end),
+        token: to_streaming_token(path, encoding, offset, offset_end),
     }
 }
 #[inline]
-fn to_streaming_token(path: String, offset: u64, offset_end: u64) -> StreamingCallbackToken {
+fn to_streaming_token(
+    path: String,
+    encoding: Option<&str>,
+    offset: u64,
+    offset_end: u64,
+) -> StreamingCallbackToken {
     StreamingCallbackToken {
         path,
         token: {
             let mut token = HashMap::new();
             token.insert("start".into(), offset.to_string()); // ! 新的位置 包含
             token.insert("end".into(), offset_end.to_string()); // ! 末尾位置 不包含
+            if let Some(encoding) = encoding {
+                token.insert("encoding".into(), encoding.to_string()); // 选中的编码
+            }
             token
         },
     }
 }
+#[allow(clippy::type_complexity)]
 #[inline]
 fn from_streaming_token(
-    StreamingCallbackToken { path, token }: StreamingCallbackToken,
-) -> Result<(String, u64, u64), ()> {
+    StreamingCallbackToken { path, mut token }: StreamingCallbackToken,
+) -> Result<(String, Option<String>, u64, u64), ()> {
     match (
         token.get("start").map(|s| s.parse()),
         token.get("end").map(|e| e.parse()),
     ) {
-        (Some(Ok(start)), Some(Ok(end))) => Ok((path, start, end)),
+        (Some(Ok(start)), Some(Ok(end))) => Ok((path, token.remove("encoding"), start, end)),
         _ => Err(()),
     }
 }
@@ -295,8 +969,8 @@ fn http_streaming(token: StreamingCallbackToken) -> StreamingCallbackHttpRespons
     //     start,
     //     end,
     // );
-    let (path, start, end) = match from_streaming_token(token) {
-        Ok((path, start, end)) => (path, start, end),
+    let (path, encoding, start, end) = match from_streaming_token(token) {
+        Ok((path, encoding, start, end)) => (path, encoding, start, end),
         _ => return StreamingCallbackHttpResponse::empty(),
     };
     if start == end {
@@ -307,9 +981,26 @@ fn http_streaming(token: StreamingCallbackToken) -> StreamingCallbackHttpRespons
         };
     }
     crate::stable::with_state(|state| {
-        let file = state.business_assets_get_file(&path);
-        if let Some(file) = file {
-            let asset = state.business_assets_get(&file.hash);
+        let target = match parse_hash_path(&path) {
+            Some(hash) => state
+                .business_assets_get(&hash)
+                .filter(|_| state.business_assets_verified(&hash))
+                .map(|asset| (hash, asset.size() as u64)), // 按 hash 访问数据
+            None => state
+                .business_assets_get_file(&path)
+                .map(|file| match &encoding {
+                    Some(encoding) => match file.encodings.get(encoding) {
+                        Some(e) => (e.hash, e.size),
+                        None => (file.hash, 0), // 编码已经不存在了
+                    },
+                    None => (file.hash, file.size),
+                }),
+        };
+        if let Some((hash, size)) = target {
+            let hash = &hash;
+            let asset = (end <= size)
+                .then(|| state.business_assets_get(hash))
+                .flatten(); // 文件已经变化了就不再继续
             if let Some(asset) = asset {
                 // 如果过长, 需要阶段显示
                 let offset = start as usize; // ! 起始位置 包含
@@ -321,10 +1012,11 @@ fn http_streaming(token: StreamingCallbackToken) -> StreamingCallbackHttpRespons
                 }
                 return StreamingCallbackHttpResponse {
                     body: asset
-                        .slice(&file.hash, file.size, offset, streaming_end - offset)
+                        .slice(hash, size, offset, streaming_end - offset)
                         .to_vec(),
-                    token: ((streaming_end as u64) < end)
-                        .then(|| to_streaming_token(path, streaming_end as u64, end)),
+                    token: ((streaming_end as u64) < end).then(|| {
+                        to_streaming_token(path, encoding.as_deref(), streaming_end as u64, end)
+                    }),
                 };
             }
         }
@@ -334,3 +1026,112 @@ fn http_streaming(token: StreamingCallbackToken) -> StreamingCallbackHttpRespons
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file() -> AssetFile {
+        let encoding = |n: u8, size: u64| AssetEncoding {
+            hash: HashDigest([n; 32]),
+            size,
+            modified: 0.into(),
+        };
+        AssetFile {
+            path: "/index.js".into(),
+            created: 0.into(),
+            modified: 0.into(),
+            headers: vec![],
+            hash: HashDigest([0; 32]),
+            size: 100,
+            encodings: [
+                ("gzip".into(), encoding(1, 30)),
+                ("br".into(), encoding(2, 20)),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    fn select(accept_encoding: Option<&str>) -> Option<String> {
+        let file = file();
+        let headers: HashMap<String, String> = accept_encoding
+            .map(|v| {
+                [("Accept-Encoding".to_string(), v.to_string())]
+                    .into_iter()
+                    .collect()
+            })
+            .unwrap_or_default();
+        select_variant(&headers, &file)
+            .encoding
+            .map(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_select_variant() {
+        assert_eq!(select(None), None);
+        assert_eq!(select(Some("identity")), None);
+        assert_eq!(select(Some("gzip")), Some("gzip".into()));
+        assert_eq!(select(Some("gzip, br")), Some("br".into())); // 服务端偏好
+        assert_eq!(select(Some("br;q=0.5, gzip;q=0.8")), Some("gzip".into())); // q 值优先
+        assert_eq!(select(Some("br;q=0, gzip")), Some("gzip".into())); // q=0 表示不接受
+        assert_eq!(select(Some("*")), Some("br".into()));
+        assert_eq!(select(Some("deflate")), None);
+    }
+
+    #[test]
+    fn test_head_content_length() {
+        let mut file = file();
+        file.size = MAX_RESPONSE_LENGTH as u64 * 2;
+        let variant = Variant {
+            encoding: None,
+            hash: &file.hash,
+            size: file.size,
+        };
+        let (mut code, mut headers) = (0, HashMap::new());
+        let (_, _, streaming_strategy) = set_headers(
+            &file.path,
+            "",
+            &HashMap::new(),
+            &file,
+            &variant,
+            true,
+            &mut code,
+            &mut headers,
+        );
+        // 超过响应长度的文件, HEAD 请求不需要流式响应, 也要给出完整的长度
+        assert!(streaming_strategy.is_none());
+        assert_eq!(
+            headers.get("Content-Length").map(|v| v.to_string()),
+            Some(file.size.to_string())
+        );
+    }
+
+    #[test]
+    fn test_http_date() {
+        const SECOND: i128 = 1_000_000_000;
+        assert_eq!(http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(http_date(-SECOND), "Thu, 01 Jan 1970 00:00:00 GMT"); // 负数按 0 处理
+        assert_eq!(
+            http_date(1445412480 * SECOND + 999),
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+        // 闰年边界
+        assert_eq!(
+            http_date(951782400 * SECOND),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+        assert_eq!(
+            http_date(1709251199 * SECOND),
+            "Thu, 29 Feb 2024 23:59:59 GMT"
+        );
+        assert_eq!(
+            http_date(1709251200 * SECOND),
+            "Fri, 01 Mar 2024 00:00:00 GMT"
+        );
+        assert_eq!(
+            http_date(4107542400 * SECOND),
+            "Mon, 01 Mar 2100 00:00:00 GMT"
+        ); // 2100 不是闰年
+    }
+}
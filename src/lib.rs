@@ -18,4 +18,10 @@ mod explore; // 核心模块
 
 mod http; // 核心模块
 
+mod metrics; // 监控指标
+
+mod gzip; // 压缩模块
+
+mod mime; // 内容类型
+
 mod common; // 由于有 candid 方法，必须放最后
@@ -0,0 +1,134 @@
+use std::{borrow::Cow, collections::HashMap, fmt::Write};
+
+use crate::stable::{Business, State};
+use crate::types::*;
+
+// Prometheus 文本格式
+// https://prometheus.io/docs/instrumenting/exposition_formats/
+pub fn metrics<'a>(headers: &mut HashMap<&'a str, Cow<'a, str>>, state: &State) -> Vec<u8> {
+    headers.insert("Content-Type", "text/plain; version=0.0.4".into());
+    headers.insert("Cache-Control", "no-store".into());
+
+    let stats = state.business_stats();
+    let counters = state.business_assets_metrics_counters();
+    let replication = state.business_replication_status();
+
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, value: u128| {
+        metric(&mut out, name, "gauge", help, &[("", value)]);
+    };
+    gauge("assets_files", "Number of files.", stats.files as u128);
+    gauge(
+        "assets_blobs",
+        "Number of unique blobs.",
+        stats.assets as u128,
+    );
+    gauge(
+        "assets_logical_bytes",
+        "Sum of file sizes.",
+        stats.logical_bytes as u128,
+    );
+    gauge(
+        "assets_encoding_bytes",
+        "Sum of encoded variant sizes.",
+        stats.encoding_bytes as u128,
+    );
+    gauge(
+        "assets_physical_bytes",
+        "Sum of unique blob sizes.",
+        stats.physical_bytes as u128,
+    );
+    gauge(
+        "assets_upload_sessions",
+        "Number of unfinished uploads.",
+        stats.uploading as u128,
+    );
+    gauge(
+        "assets_upload_session_bytes",
+        "Bytes held by unfinished uploads.",
+        stats.uploading_bytes as u128,
+    );
+    gauge(
+        "assets_quarantined_bytes",
+        "Bytes held by quarantined blobs.",
+        stats.quarantined_bytes as u128,
+    );
+    gauge(
+        "assets_replication_lag",
+        "Source changes not applied yet.",
+        replication.lag as u128,
+    );
+    gauge(
+        "assets_cycles_balance",
+        "Cycles balance.",
+        ic_cdk::api::canister_balance128(),
+    );
+    gauge(
+        "assets_heap_memory_bytes",
+        "Heap memory size.",
+        stats.heap_bytes as u128,
+    );
+    gauge(
+        "assets_stable_memory_bytes",
+        "Stable memory size.",
+        stats.stable_bytes as u128,
+    );
+    gauge(
+        "assets_records",
+        "Number of operation records.",
+        state.record_find_all().len() as u128,
+    );
+    gauge(
+        "assets_paused",
+        "1 if the canister is paused.",
+        state.pause_is_paused() as u128,
+    );
+
+    metric(
+        &mut out,
+        "assets_changes_total",
+        "counter",
+        "File changes by operation.",
+        &[
+            ("op=\"put\"", counters.put_files as u128),
+            ("op=\"put_encoding\"", counters.put_encodings as u128),
+            ("op=\"delete\"", counters.deleted_files as u128),
+            ("op=\"delete_encoding\"", counters.deleted_encodings as u128),
+        ],
+    );
+    metric(
+        &mut out,
+        "assets_upload_chunks_total",
+        "counter",
+        "Uploaded chunks.",
+        &[("", counters.upload_chunks as u128)],
+    );
+    metric(
+        &mut out,
+        "assets_upload_bytes_total",
+        "counter",
+        "Uploaded bytes.",
+        &[("", counters.upload_bytes as u128)],
+    );
+    metric(
+        &mut out,
+        "assets_quarantined_total",
+        "counter",
+        "Quarantined blobs.",
+        &[("", counters.quarantined as u128)],
+    );
+
+    out.into_bytes()
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, values: &[(&str, u128)]) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in values {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{name} {value}");
+        } else {
+            let _ = writeln!(out, "{name}{{{labels}}} {value}");
+        }
+    }
+}
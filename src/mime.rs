@@ -0,0 +1,250 @@
+// 根据拓展名设置对应的 Content-Type 内容
+const EXT_CONTENT_TYPES: [(&str, &str); 57] = [
+    ("txt", "text/plain"), // 文本
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("htx", "text/html"),
+    ("xhtml", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("md", "text/markdown"),
+    ("ics", "text/calendar"),
+    ("csv", "text/csv"),
+    ("xml", "text/xml"),
+    ("json", "application/json"), // 应用
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("7z", "application/x-7z-compressed"),
+    ("eot", "application/vnd.ms-fontobject"), // 字体// cspell: disable-line
+    ("png", "image/png"),                     // 图片
+    ("apng", "image/apng"),
+    ("gif", "image/gif"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("svg", "image/svg+xml"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("ico", "image/x-icon"),
+    ("mp4", "video/mp4"),       // 视频
+    ("avi", "video/x-msvideo"), // cspell: disable-line
+    ("mov", "video/quicktime"), // cspell: disable-line
+    ("mpeg", "video/mpeg"),
+    ("ogv", "video/ogg"),
+    ("webm", "video/webm"),
+    ("mp3", "audio/mpeg"), // 音频
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"), // cspell: disable-line
+    ("aac", "audio/aac"),
+    ("oga", "audio/ogg"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/opus"),
+    ("wma", "audio/x-ms-wma"),
+    ("mid", "audio/midi"),
+    ("midi", "audio/midi"),
+    ("ra", "audio/x-realaudio"),  // cspell: disable-line
+    ("ram", "audio/x-realaudio"), // cspell: disable-line
+    ("m4a", "audio/mp4"),
+    ("otf", "font/otf"), // 字体
+    ("ttf", "font/ttf"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("bin", "application/octet-stream"), // 其他
+];
+
+// 根据文件头部的特征字节推断
+const MAGIC_CONTENT_TYPES: [(&[u8], &str); 13] = [
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\0asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"ID3", "audio/mpeg"),
+    (b"fLaC", "audio/flac"), // cspell: disable-line
+];
+
+// 根据拓展名推断
+fn by_extension(ext: &str) -> Option<&'static str> {
+    EXT_CONTENT_TYPES
+        .iter()
+        .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+        .map(|(_, content_type)| *content_type)
+}
+
+// 根据数据推断
+fn by_magic(head: &[u8]) -> Option<&'static str> {
+    if let Some((_, content_type)) = MAGIC_CONTENT_TYPES
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+    {
+        return Some(content_type);
+    }
+    if head.len() >= 12 && &head[0..4] == b"RIFF" {
+        match &head[8..12] {
+            b"WEBP" => return Some("image/webp"),
+            b"WAVE" => return Some("audio/wav"),
+            b"AVI " => return Some("video/x-msvideo"), // cspell: disable-line
+            _ => {}
+        }
+    }
+    if head.len() >= 12 && &head[4..8] == b"ftyp" {
+        return Some(match &head[8..12] {
+            b"avif" | b"avis" => "image/avif", // cspell: disable-line
+            b"qt  " => "video/quicktime",
+            b"M4A " => "audio/mp4",
+            _ => "video/mp4",
+        });
+    }
+    // 文本内容
+    let text = std::str::from_utf8(head)
+        .or_else(|e| std::str::from_utf8(&head[..e.valid_up_to()]))
+        .ok()?;
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let lower = text.chars().take(64).collect::<String>().to_lowercase();
+    if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        return Some("text/html");
+    }
+    if lower.starts_with("<svg") {
+        return Some("image/svg+xml");
+    }
+    if lower.starts_with("<?xml") {
+        return Some(if text.contains("<svg") {
+            "image/svg+xml"
+        } else {
+            "text/xml"
+        });
+    }
+    if lower.starts_with('{') || lower.starts_with('[') {
+        return Some("application/json");
+    }
+    None
+}
+
+/// 推断文件的内容类型和内容编码
+/// 先根据拓展名, .gz 文件取前面的拓展名作为内容类型, 并使用 gzip 编码
+/// 拓展名无法识别时, 再根据数据头部的特征字节推断
+pub fn infer_content_type(path: &str, head: &[u8]) -> Option<(&'static str, Option<&'static str>)> {
+    let name = path.rsplit('/').next().unwrap_or(path); // 文件名
+    let mut exts = name.rsplit('.');
+    let ext = exts.next().filter(|_| name.contains('.'));
+    if let Some(ext) = ext {
+        if ext.eq_ignore_ascii_case("gz") {
+            // gz 需要额外取前面的拓展名
+            let inner = exts
+                .next()
+                .filter(|_| 1 < name.matches('.').count())
+                .and_then(by_extension)
+                .filter(|content_type| *content_type != "application/x-tar");
+            if let Some(content_type) = inner {
+                return Some((content_type, Some("gzip")));
+            }
+        }
+        if let Some(content_type) = by_extension(ext) {
+            return Some((content_type, None));
+        }
+    }
+    by_magic(head).map(|content_type| (content_type, None))
+}
+
+/// 推断时需要的数据头部长度
+pub const MAGIC_HEAD_SIZE: usize = 512;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_by_extension() {
+        assert_eq!(
+            infer_content_type("/a/index.html", b""),
+            Some(("text/html", None))
+        );
+        assert_eq!(
+            infer_content_type("/a/APP.JS", b""),
+            Some(("text/javascript", None))
+        );
+        assert_eq!(
+            infer_content_type("/font.woff2", b""),
+            Some(("font/woff2", None))
+        );
+        // 拓展名优先于数据特征
+        assert_eq!(
+            infer_content_type("/a.txt", b"\x89PNG\r\n\x1a\n"),
+            Some(("text/plain", None))
+        );
+        // 拓展名表中没有重复的项
+        for (i, (ext, _)) in EXT_CONTENT_TYPES.iter().enumerate() {
+            assert!(
+                !EXT_CONTENT_TYPES[..i].iter().any(|(e, _)| e == ext),
+                "{ext}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_gz_double_extension() {
+        assert_eq!(
+            infer_content_type("/app.js.gz", b"\x1f\x8b"),
+            Some(("text/javascript", Some("gzip")))
+        );
+        assert_eq!(
+            infer_content_type("/data.JSON.GZ", b""),
+            Some(("application/json", Some("gzip")))
+        );
+        // tar.gz 和单独的 gz 文件是压缩包本身
+        assert_eq!(
+            infer_content_type("/a.tar.gz", b""),
+            Some(("application/gzip", None))
+        );
+        assert_eq!(
+            infer_content_type("/a.gz", b""),
+            Some(("application/gzip", None))
+        );
+        assert_eq!(
+            infer_content_type("/a.unknown.gz", b""),
+            Some(("application/gzip", None))
+        );
+    }
+
+    #[test]
+    fn test_by_magic() {
+        let infer = |head: &[u8]| infer_content_type("/no-extension", head).map(|(t, _)| t);
+        assert_eq!(infer(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+        assert_eq!(infer(b"\xff\xd8\xff\xe0"), Some("image/jpeg"));
+        assert_eq!(infer(b"\0asm\x01\0\0\0"), Some("application/wasm"));
+        assert_eq!(infer(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(infer(b"\0\0\0\x20ftypisom"), Some("video/mp4"));
+        assert_eq!(infer(b"\0\0\0\x20ftypavif"), Some("image/avif"));
+        assert_eq!(
+            infer(b"\xef\xbb\xbf  <!DOCTYPE html><html>"),
+            Some("text/html")
+        );
+        assert_eq!(
+            infer(b"<?xml version=\"1.0\"?><svg>"),
+            Some("image/svg+xml")
+        );
+        assert_eq!(infer(b"<?xml version=\"1.0\"?><rss>"), Some("text/xml"));
+        assert_eq!(infer(b" {\"a\": 1}"), Some("application/json"));
+        assert_eq!(infer(b"plain words"), None);
+        assert_eq!(infer(b"\xff\xfe\xfd"), None);
+        // 截断在多字节字符中间也能识别
+        assert_eq!(
+            infer("<html>中".as_bytes().split_at(8).0),
+            Some("text/html")
+        );
+        assert_eq!(infer(b""), None);
+    }
+}
@@ -23,6 +23,9 @@ pub trait Business:
     fn business_hashed_find(&self) -> bool {
         panic!("Not supported operation by this version.")
     }
+    fn business_verify_find(&self) -> bool {
+        panic!("Not supported operation by this version.")
+    }
     fn business_files(&self) -> Vec<crate::stable::QueryFile> {
         panic!("Not supported operation by this version.")
     }
@@ -32,19 +35,182 @@ pub trait Business:
     fn business_download_by(&self, path: String, offset: u64, size: u64) -> Vec<u8> {
         panic!("Not supported operation by this version.")
     }
+    fn business_download_by_hash(&self, hash: crate::stable::HashDigest) -> Vec<u8> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_download_start(
+        &self,
+        path: String,
+        encoding: Option<String>,
+    ) -> Result<crate::stable::QueryDownloading, crate::stable::DownloadingError> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_download_chunk(
+        &self,
+        arg: crate::stable::DownloadingArg,
+    ) -> Result<Vec<u8>, crate::stable::DownloadingError> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_download_chunk_by_hash(
+        &self,
+        arg: crate::stable::DownloadingHashArg,
+    ) -> Result<Vec<u8>, crate::stable::DownloadingError> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_file(&self, path: String) -> Option<crate::stable::QueryFile> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_file_by_hash(
+        &self,
+        hash: crate::stable::HashDigest,
+    ) -> Option<crate::stable::QueryFile> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_uploading(
+        &self,
+        path: String,
+        encoding: Option<String>,
+    ) -> Option<crate::stable::QueryUploading> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_quarantined(&self) -> Vec<crate::stable::QueryQuarantined> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_routing_find(&self) -> crate::stable::RoutingSettings {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_error_pages_find(&self) -> Vec<crate::stable::ErrorPage> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_maintenance_find(&self) -> crate::stable::MaintenanceSettings {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_route_rules_find(&self) -> Vec<crate::stable::RouteRule> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_header_rules_find(&self) -> Vec<crate::stable::HeaderRule> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_cors_rules_find(&self) -> Vec<crate::stable::CorsRule> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_explore_find(&self) -> crate::stable::ExploreSettings {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_manifest_find(&self) -> crate::stable::ManifestSettings {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_change_retention_find(&self) -> u64 {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_changes_since(
+        &self,
+        since: u64,
+        limit: u32,
+    ) -> Result<crate::stable::QueryChanges, crate::stable::ChangesError> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_changes_snapshot(
+        &self,
+        after: Option<String>,
+        limit: u32,
+    ) -> crate::stable::QueryChangesSnapshot {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_replication_find(&self) -> crate::stable::ReplicationSettings {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_replication_status(&self) -> crate::stable::QueryReplication {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_subscriptions_find(&self) -> Vec<crate::stable::Subscription> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_audit_find(&self) -> crate::stable::AuditSettings {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_audit_report(&self) -> Option<crate::stable::AuditReport> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_stats(&self) -> crate::stable::QueryStats {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_metrics_find(&self) -> crate::stable::MetricsSettings {
+        panic!("Not supported operation by this version.")
+    }
 
     // 对外的修改接口
     fn business_hashed_update(&mut self, hashed: bool) {
         panic!("Not supported operation by this version.")
     }
-    fn business_upload(&mut self, args: Vec<crate::stable::UploadingArg>) {
+    fn business_verify_update(&mut self, verify: bool) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_upload(
+        &mut self,
+        args: Vec<crate::stable::UploadingArg>,
+    ) -> Result<(), crate::stable::UploadingError> {
         panic!("Not supported operation by this version.")
     }
     fn business_delete(&mut self, names: Vec<String>) {
         panic!("Not supported operation by this version.")
     }
+    fn business_quarantine_delete(&mut self, hashes: Vec<crate::stable::HashDigest>) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_routing_update(&mut self, routing: crate::stable::RoutingSettings) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_error_pages_update(&mut self, error_pages: Vec<crate::stable::ErrorPage>) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_maintenance_update(&mut self, maintenance: crate::stable::MaintenanceSettings) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_route_rules_update(
+        &mut self,
+        route_rules: Vec<crate::stable::RouteRule>,
+    ) -> Result<(), String> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_header_rules_update(
+        &mut self,
+        header_rules: Vec<crate::stable::HeaderRule>,
+    ) -> Result<(), String> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_cors_rules_update(&mut self, cors_rules: Vec<crate::stable::CorsRule>) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_explore_update(&mut self, explore: crate::stable::ExploreSettings) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_manifest_update(&mut self, manifest: crate::stable::ManifestSettings) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_change_retention_update(&mut self, retention: u64) -> Result<(), String> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_replication_update(&mut self, replication: crate::stable::ReplicationSettings) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_subscriptions_update(
+        &mut self,
+        subscriptions: Vec<crate::stable::Subscription>,
+    ) -> Result<(), String> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_audit_update(&mut self, audit: crate::stable::AuditSettings) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_metrics_update(&mut self, metrics: crate::stable::MetricsSettings) {
+        panic!("Not supported operation by this version.")
+    }
 
     // 内部使用的接口
+    fn business_assets_files(&self) -> Vec<&crate::stable::AssetFile> {
+        panic!("Not supported operation by this version.")
+    }
     fn business_assets_get_file(&self, path: &str) -> Option<&crate::stable::AssetFile> {
         panic!("Not supported operation by this version.")
     }
@@ -54,6 +220,81 @@ pub trait Business:
     ) -> Option<&crate::stable::AssetData> {
         panic!("Not supported operation by this version.")
     }
+    fn business_assets_get_file_by_hash(
+        &self,
+        hash: &crate::stable::HashDigest,
+    ) -> Option<&crate::stable::AssetFile> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_assets_verified(&self, hash: &crate::stable::HashDigest) -> bool {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_assets_manifest(&self) -> Option<std::borrow::Cow<'_, [u8]>> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_assets_metrics_counters(&self) -> &crate::stable::MetricsCounters {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_assets_get_error_page(
+        &self,
+        path: &str,
+        status: u16,
+    ) -> Option<&crate::stable::ErrorPage> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_assets_route(&self, path: &str) -> Option<crate::stable::RouteAction> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_assets_header_rules(&self, path: &str) -> Vec<&crate::stable::HeaderRule> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_assets_cors_rule(&self, path: &str) -> Option<&crate::stable::CorsRule> {
+        panic!("Not supported operation by this version.")
+    }
+
+    // 同步副本
+    fn business_replicate_begin(&mut self) -> Option<(CanisterId, u64, u32)> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_replicate_end(&mut self, source: CanisterId, error: Option<String>) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_replicate_latest(&mut self, source: CanisterId, latest: u64) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_replicate_skip(&mut self, source: CanisterId, seq: u64) -> Result<(), String> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_replicate_apply(
+        &mut self,
+        source: CanisterId,
+        change: crate::stable::AssetChange,
+        data: Option<Vec<u8>>,
+    ) -> Result<(), String> {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_replicate_snapshot_done(
+        &mut self,
+        source: CanisterId,
+        seq: u64,
+        paths: HashSet<String>,
+    ) -> Result<(), String> {
+        panic!("Not supported operation by this version.")
+    }
+
+    // 定时任务使用的接口
+    fn business_verify_hashes(&mut self, budget: &mut usize) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_compress_files(&mut self, budget: &mut usize) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_notify_subscribers(&mut self) {
+        panic!("Not supported operation by this version.")
+    }
+    fn business_audit_assets(&mut self, budget: &mut usize) {
+        panic!("Not supported operation by this version.")
+    }
 }
 
 // 业务实现
@@ -61,6 +302,9 @@ impl Business for State {
     fn business_hashed_find(&self) -> bool {
         self.get().business_hashed_find()
     }
+    fn business_verify_find(&self) -> bool {
+        self.get().business_verify_find()
+    }
     fn business_files(&self) -> Vec<QueryFile> {
         self.get().business_files()
     }
@@ -70,21 +314,228 @@ impl Business for State {
     fn business_download_by(&self, path: String, offset: u64, size: u64) -> Vec<u8> {
         self.get().business_download_by(path, offset, size)
     }
+    fn business_download_by_hash(&self, hash: HashDigest) -> Vec<u8> {
+        self.get().business_download_by_hash(hash)
+    }
+    fn business_download_start(
+        &self,
+        path: String,
+        encoding: Option<String>,
+    ) -> Result<QueryDownloading, DownloadingError> {
+        self.get().business_download_start(path, encoding)
+    }
+    fn business_download_chunk(&self, arg: DownloadingArg) -> Result<Vec<u8>, DownloadingError> {
+        self.get().business_download_chunk(arg)
+    }
+    fn business_download_chunk_by_hash(
+        &self,
+        arg: DownloadingHashArg,
+    ) -> Result<Vec<u8>, DownloadingError> {
+        self.get().business_download_chunk_by_hash(arg)
+    }
+    fn business_file(&self, path: String) -> Option<QueryFile> {
+        self.get().business_file(path)
+    }
+    fn business_file_by_hash(&self, hash: HashDigest) -> Option<QueryFile> {
+        self.get().business_file_by_hash(hash)
+    }
+    fn business_uploading(&self, path: String, encoding: Option<String>) -> Option<QueryUploading> {
+        self.get().business_uploading(path, encoding)
+    }
+    fn business_quarantined(&self) -> Vec<QueryQuarantined> {
+        self.get().business_quarantined()
+    }
+    fn business_routing_find(&self) -> RoutingSettings {
+        self.get().business_routing_find()
+    }
+    fn business_error_pages_find(&self) -> Vec<ErrorPage> {
+        self.get().business_error_pages_find()
+    }
+    fn business_maintenance_find(&self) -> MaintenanceSettings {
+        self.get().business_maintenance_find()
+    }
+    fn business_route_rules_find(&self) -> Vec<RouteRule> {
+        self.get().business_route_rules_find()
+    }
+    fn business_header_rules_find(&self) -> Vec<HeaderRule> {
+        self.get().business_header_rules_find()
+    }
+    fn business_cors_rules_find(&self) -> Vec<CorsRule> {
+        self.get().business_cors_rules_find()
+    }
+    fn business_explore_find(&self) -> ExploreSettings {
+        self.get().business_explore_find()
+    }
+    fn business_manifest_find(&self) -> ManifestSettings {
+        self.get().business_manifest_find()
+    }
+    fn business_change_retention_find(&self) -> u64 {
+        self.get().business_change_retention_find()
+    }
+    fn business_changes_since(&self, since: u64, limit: u32) -> Result<QueryChanges, ChangesError> {
+        self.get().business_changes_since(since, limit)
+    }
+    fn business_changes_snapshot(&self, after: Option<String>, limit: u32) -> QueryChangesSnapshot {
+        self.get().business_changes_snapshot(after, limit)
+    }
+    fn business_replication_find(&self) -> ReplicationSettings {
+        self.get().business_replication_find()
+    }
+    fn business_replication_status(&self) -> QueryReplication {
+        self.get().business_replication_status()
+    }
+    fn business_subscriptions_find(&self) -> Vec<Subscription> {
+        self.get().business_subscriptions_find()
+    }
+    fn business_audit_find(&self) -> AuditSettings {
+        self.get().business_audit_find()
+    }
+    fn business_audit_report(&self) -> Option<AuditReport> {
+        self.get().business_audit_report()
+    }
+    fn business_stats(&self) -> QueryStats {
+        self.get().business_stats()
+    }
+    fn business_metrics_find(&self) -> MetricsSettings {
+        self.get().business_metrics_find()
+    }
 
     fn business_hashed_update(&mut self, hashed: bool) {
         self.get_mut().business_hashed_update(hashed)
     }
-    fn business_upload(&mut self, args: Vec<UploadingArg>) {
+    fn business_verify_update(&mut self, verify: bool) {
+        self.get_mut().business_verify_update(verify)
+    }
+    fn business_upload(&mut self, args: Vec<UploadingArg>) -> Result<(), UploadingError> {
         self.get_mut().business_upload(args)
     }
     fn business_delete(&mut self, names: Vec<String>) {
         self.get_mut().business_delete(names)
     }
+    fn business_quarantine_delete(&mut self, hashes: Vec<HashDigest>) {
+        self.get_mut().business_quarantine_delete(hashes)
+    }
+    fn business_routing_update(&mut self, routing: RoutingSettings) {
+        self.get_mut().business_routing_update(routing)
+    }
+    fn business_error_pages_update(&mut self, error_pages: Vec<ErrorPage>) {
+        self.get_mut().business_error_pages_update(error_pages)
+    }
+    fn business_maintenance_update(&mut self, maintenance: MaintenanceSettings) {
+        self.get_mut().business_maintenance_update(maintenance)
+    }
+    fn business_route_rules_update(&mut self, route_rules: Vec<RouteRule>) -> Result<(), String> {
+        self.get_mut().business_route_rules_update(route_rules)
+    }
+    fn business_header_rules_update(
+        &mut self,
+        header_rules: Vec<HeaderRule>,
+    ) -> Result<(), String> {
+        self.get_mut().business_header_rules_update(header_rules)
+    }
+    fn business_cors_rules_update(&mut self, cors_rules: Vec<CorsRule>) {
+        self.get_mut().business_cors_rules_update(cors_rules)
+    }
+    fn business_explore_update(&mut self, explore: ExploreSettings) {
+        self.get_mut().business_explore_update(explore)
+    }
+    fn business_manifest_update(&mut self, manifest: ManifestSettings) {
+        self.get_mut().business_manifest_update(manifest)
+    }
+    fn business_change_retention_update(&mut self, retention: u64) -> Result<(), String> {
+        self.get_mut().business_change_retention_update(retention)
+    }
+    fn business_replication_update(&mut self, replication: ReplicationSettings) {
+        self.get_mut().business_replication_update(replication)
+    }
+    fn business_subscriptions_update(
+        &mut self,
+        subscriptions: Vec<Subscription>,
+    ) -> Result<(), String> {
+        self.get_mut().business_subscriptions_update(subscriptions)
+    }
+    fn business_audit_update(&mut self, audit: AuditSettings) {
+        self.get_mut().business_audit_update(audit)
+    }
+    fn business_metrics_update(&mut self, metrics: MetricsSettings) {
+        self.get_mut().business_metrics_update(metrics)
+    }
 
+    fn business_assets_files(&self) -> Vec<&AssetFile> {
+        self.get().business_assets_files()
+    }
     fn business_assets_get_file(&self, path: &str) -> Option<&AssetFile> {
         self.get().business_assets_get_file(path)
     }
     fn business_assets_get(&self, hash: &HashDigest) -> Option<&AssetData> {
         self.get().business_assets_get(hash)
     }
+    fn business_assets_get_file_by_hash(&self, hash: &HashDigest) -> Option<&AssetFile> {
+        self.get().business_assets_get_file_by_hash(hash)
+    }
+    fn business_assets_verified(&self, hash: &HashDigest) -> bool {
+        self.get().business_assets_verified(hash)
+    }
+    fn business_assets_manifest(&self) -> Option<std::borrow::Cow<'_, [u8]>> {
+        self.get().business_assets_manifest()
+    }
+    fn business_assets_metrics_counters(&self) -> &MetricsCounters {
+        self.get().business_assets_metrics_counters()
+    }
+    fn business_assets_get_error_page(&self, path: &str, status: u16) -> Option<&ErrorPage> {
+        self.get().business_assets_get_error_page(path, status)
+    }
+    fn business_assets_route(&self, path: &str) -> Option<RouteAction> {
+        self.get().business_assets_route(path)
+    }
+    fn business_assets_header_rules(&self, path: &str) -> Vec<&HeaderRule> {
+        self.get().business_assets_header_rules(path)
+    }
+    fn business_assets_cors_rule(&self, path: &str) -> Option<&CorsRule> {
+        self.get().business_assets_cors_rule(path)
+    }
+
+    fn business_replicate_begin(&mut self) -> Option<(CanisterId, u64, u32)> {
+        self.get_mut().business_replicate_begin()
+    }
+    fn business_replicate_end(&mut self, source: CanisterId, error: Option<String>) {
+        self.get_mut().business_replicate_end(source, error)
+    }
+    fn business_replicate_latest(&mut self, source: CanisterId, latest: u64) {
+        self.get_mut().business_replicate_latest(source, latest)
+    }
+    fn business_replicate_skip(&mut self, source: CanisterId, seq: u64) -> Result<(), String> {
+        self.get_mut().business_replicate_skip(source, seq)
+    }
+    fn business_replicate_apply(
+        &mut self,
+        source: CanisterId,
+        change: AssetChange,
+        data: Option<Vec<u8>>,
+    ) -> Result<(), String> {
+        self.get_mut()
+            .business_replicate_apply(source, change, data)
+    }
+    fn business_replicate_snapshot_done(
+        &mut self,
+        source: CanisterId,
+        seq: u64,
+        paths: HashSet<String>,
+    ) -> Result<(), String> {
+        self.get_mut()
+            .business_replicate_snapshot_done(source, seq, paths)
+    }
+
+    fn business_verify_hashes(&mut self, budget: &mut usize) {
+        self.get_mut().business_verify_hashes(budget)
+    }
+    fn business_compress_files(&mut self, budget: &mut usize) {
+        self.get_mut().business_compress_files(budget)
+    }
+    fn business_notify_subscribers(&mut self) {
+        self.get_mut().business_notify_subscribers()
+    }
+    fn business_audit_assets(&mut self, budget: &mut usize) {
+        self.get_mut().business_audit_assets(budget)
+    }
 }
@@ -25,14 +25,16 @@ impl std::error::Error for ParsePermissionError<'_> {}
 
 mod v000;
 mod v001;
+mod v002;
 
 // ! 此处应该是最新的版本
 // !     👇👇 UPGRADE WARNING: 必须是当前代码的版本
-pub use v001::types::*;
+pub use v002::types::*;
 
 pub enum State {
     V0(Box<v000::types::InnerState>),
     V1(Box<v001::types::InnerState>),
+    V2(Box<v002::types::InnerState>),
     // * 👆👆 UPGRADE WARNING: 引入新版本
 }
 
@@ -45,6 +47,7 @@ impl Upgrade for State {
         match self {
             V0(_) => 0,
             V1(_) => 1,
+            V2(_) => 2,
             // * 👆👆! 升级需要在此添加版本号
         }
     }
@@ -53,6 +56,7 @@ impl Upgrade for State {
         match version {
             0 => V0(Box::default()), // * 初始化
             1 => V1(Box::default()), // * 初始化
+            2 => V2(Box::default()), // * 初始化
             // ! 👆👆 新增版本需要添加默认的数据
             _ => {
                 #[allow(clippy::panic)] // ? SAFETY
@@ -68,7 +72,8 @@ impl Upgrade for State {
             // 进行升级操作, 不断地升到下一版本
             match self {
                 V0(s) => *self = V1(std::mem::take(&mut *s).into()), // -> V1
-                V1(_) => break 'outer,                               // same version do nothing
+                V1(s) => *self = V2(std::mem::take(&mut *s).into()), // -> V2
+                V2(_) => break 'outer,                               // same version do nothing
             }
         }
     }
@@ -79,12 +84,14 @@ impl State {
         match self {
             V0(s) => s.as_ref(), // * 获取不可变对象
             V1(s) => s.as_ref(), // * 获取不可变对象
+            V2(s) => s.as_ref(), // * 获取不可变对象
         }
     }
     pub fn get_mut(&mut self) -> &mut dyn Business {
         match self {
             V0(s) => s.as_mut(), // * 获取可变对象
             V1(s) => s.as_mut(), // * 获取可变对象
+            V2(s) => s.as_mut(), // * 获取可变对象
         }
     }
 }
@@ -109,6 +116,7 @@ impl Initial<CanisterInitialArg> for State {
         match self {
             V0(s) => s.init(arg), // * 初始化
             V1(s) => s.init(arg), // * 初始化
+            V2(s) => s.init(arg), // * 初始化
         }
     }
 }
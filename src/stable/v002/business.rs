@@ -0,0 +1,258 @@
+use super::super::business::*;
+use super::types::*;
+
+#[allow(unused_variables)]
+impl Business for InnerState {
+    fn business_hashed_find(&self) -> bool {
+        self.hashed
+    }
+    fn business_verify_find(&self) -> bool {
+        self.verify
+    }
+    fn business_files(&self) -> Vec<QueryFile> {
+        self.files()
+    }
+    fn business_download(&self, path: String) -> Vec<u8> {
+        self.download(path)
+    }
+    fn business_download_by(&self, path: String, offset: u64, size: u64) -> Vec<u8> {
+        self.download_by(path, offset, size)
+    }
+    fn business_download_by_hash(&self, hash: HashDigest) -> Vec<u8> {
+        self.download_by_hash(hash)
+    }
+    fn business_download_start(
+        &self,
+        path: String,
+        encoding: Option<String>,
+    ) -> Result<QueryDownloading, DownloadingError> {
+        self.download_start(path, encoding)
+    }
+    fn business_download_chunk(&self, arg: DownloadingArg) -> Result<Vec<u8>, DownloadingError> {
+        self.download_chunk(arg)
+    }
+    fn business_download_chunk_by_hash(
+        &self,
+        arg: DownloadingHashArg,
+    ) -> Result<Vec<u8>, DownloadingError> {
+        self.download_chunk_by_hash(arg)
+    }
+    fn business_file(&self, path: String) -> Option<QueryFile> {
+        self.file(&path)
+    }
+    fn business_file_by_hash(&self, hash: HashDigest) -> Option<QueryFile> {
+        self.file_by_hash(&hash)
+    }
+    fn business_uploading(&self, path: String, encoding: Option<String>) -> Option<QueryUploading> {
+        self.uploading(&path, encoding)
+    }
+    fn business_quarantined(&self) -> Vec<QueryQuarantined> {
+        self.quarantined()
+    }
+    fn business_routing_find(&self) -> RoutingSettings {
+        self.routing.clone()
+    }
+    fn business_error_pages_find(&self) -> Vec<ErrorPage> {
+        self.error_pages.clone()
+    }
+    fn business_maintenance_find(&self) -> MaintenanceSettings {
+        self.maintenance.clone()
+    }
+    fn business_route_rules_find(&self) -> Vec<RouteRule> {
+        self.route_rules.clone()
+    }
+    fn business_header_rules_find(&self) -> Vec<HeaderRule> {
+        self.header_rules.clone()
+    }
+    fn business_cors_rules_find(&self) -> Vec<CorsRule> {
+        self.cors_rules.clone()
+    }
+    fn business_explore_find(&self) -> ExploreSettings {
+        self.explore.clone()
+    }
+    fn business_manifest_find(&self) -> ManifestSettings {
+        self.manifest.clone()
+    }
+    fn business_change_retention_find(&self) -> u64 {
+        self.change_retention()
+    }
+    fn business_changes_since(&self, since: u64, limit: u32) -> Result<QueryChanges, ChangesError> {
+        self.changes_since(since, limit)
+    }
+    fn business_changes_snapshot(&self, after: Option<String>, limit: u32) -> QueryChangesSnapshot {
+        self.changes_snapshot(after, limit)
+    }
+    fn business_replication_find(&self) -> ReplicationSettings {
+        self.replication_settings()
+    }
+    fn business_replication_status(&self) -> QueryReplication {
+        self.replication_status()
+    }
+    fn business_subscriptions_find(&self) -> Vec<Subscription> {
+        self.subscriptions.clone()
+    }
+    fn business_audit_find(&self) -> AuditSettings {
+        self.audit.clone()
+    }
+    fn business_audit_report(&self) -> Option<AuditReport> {
+        self.audit_report()
+    }
+    fn business_stats(&self) -> QueryStats {
+        self.stats()
+    }
+    fn business_metrics_find(&self) -> MetricsSettings {
+        self.metrics.clone()
+    }
+
+    fn business_hashed_update(&mut self, hashed: bool) {
+        self.hashed = hashed;
+    }
+    fn business_verify_update(&mut self, verify: bool) {
+        self.verify_update(verify);
+    }
+    fn business_upload(&mut self, args: Vec<UploadingArg>) -> Result<(), UploadingError> {
+        let chunk_hashes = self.check_uploading(&args)?; // 有错误时整批都不保存
+        let result = args
+            .into_iter()
+            .zip(chunk_hashes)
+            .try_for_each(|(arg, chunk_hash)| self.put_uploading(arg, chunk_hash));
+        self.refresh_manifest();
+        self.notify_subscribers();
+        result
+    }
+    fn business_delete(&mut self, names: Vec<String>) {
+        for name in names {
+            self.clean_uploading(&name);
+            self.clean_file(&name);
+        }
+        self.refresh_manifest();
+        self.notify_subscribers();
+    }
+    fn business_quarantine_delete(&mut self, hashes: Vec<HashDigest>) {
+        self.quarantine_delete(hashes);
+    }
+    fn business_routing_update(&mut self, routing: RoutingSettings) {
+        self.routing_update(routing);
+    }
+    fn business_error_pages_update(&mut self, error_pages: Vec<ErrorPage>) {
+        self.error_pages = error_pages;
+    }
+    fn business_maintenance_update(&mut self, maintenance: MaintenanceSettings) {
+        self.maintenance = maintenance;
+    }
+    fn business_route_rules_update(&mut self, route_rules: Vec<RouteRule>) -> Result<(), String> {
+        self.route_rules_update(route_rules)
+    }
+    fn business_header_rules_update(
+        &mut self,
+        header_rules: Vec<HeaderRule>,
+    ) -> Result<(), String> {
+        self.header_rules_update(header_rules)
+    }
+    fn business_cors_rules_update(&mut self, cors_rules: Vec<CorsRule>) {
+        self.cors_rules = cors_rules;
+    }
+    fn business_explore_update(&mut self, explore: ExploreSettings) {
+        self.explore_update(explore);
+    }
+    fn business_manifest_update(&mut self, manifest: ManifestSettings) {
+        self.manifest_update(manifest);
+    }
+    fn business_change_retention_update(&mut self, retention: u64) -> Result<(), String> {
+        self.change_retention_update(retention)
+    }
+    fn business_replication_update(&mut self, replication: ReplicationSettings) {
+        self.replication_update(replication);
+    }
+    fn business_subscriptions_update(
+        &mut self,
+        subscriptions: Vec<Subscription>,
+    ) -> Result<(), String> {
+        self.subscriptions_update(subscriptions)
+    }
+    fn business_audit_update(&mut self, audit: AuditSettings) {
+        self.audit_update(audit);
+    }
+    fn business_metrics_update(&mut self, metrics: MetricsSettings) {
+        self.metrics = metrics;
+    }
+
+    fn business_assets_files(&self) -> Vec<&AssetFile> {
+        self.files.values().collect()
+    }
+    fn business_assets_get_file(&self, path: &str) -> Option<&AssetFile> {
+        self.files.get(path)
+    }
+    fn business_assets_get(&self, hash: &HashDigest) -> Option<&AssetData> {
+        self.assets.get(hash)
+    }
+    fn business_assets_get_file_by_hash(&self, hash: &HashDigest) -> Option<&AssetFile> {
+        self.find_file_by_hash(hash)
+    }
+    fn business_assets_verified(&self, hash: &HashDigest) -> bool {
+        self.is_verified(hash)
+    }
+    fn business_assets_manifest(&self) -> Option<std::borrow::Cow<'_, [u8]>> {
+        self.get_manifest()
+    }
+    fn business_assets_metrics_counters(&self) -> &MetricsCounters {
+        self.metrics_counters()
+    }
+    fn business_assets_get_error_page(&self, path: &str, status: u16) -> Option<&ErrorPage> {
+        self.find_error_page(path, status)
+    }
+    fn business_assets_route(&self, path: &str) -> Option<RouteAction> {
+        self.route(path)
+    }
+    fn business_assets_header_rules(&self, path: &str) -> Vec<&HeaderRule> {
+        self.find_header_rules(path)
+    }
+    fn business_assets_cors_rule(&self, path: &str) -> Option<&CorsRule> {
+        self.find_cors_rule(path)
+    }
+
+    fn business_replicate_begin(&mut self) -> Option<(CanisterId, u64, u32)> {
+        self.replicate_begin()
+    }
+    fn business_replicate_end(&mut self, source: CanisterId, error: Option<String>) {
+        self.replicate_end(source, error);
+    }
+    fn business_replicate_latest(&mut self, source: CanisterId, latest: u64) {
+        self.replicate_latest(source, latest);
+    }
+    fn business_replicate_skip(&mut self, source: CanisterId, seq: u64) -> Result<(), String> {
+        self.replicate_skip(source, seq)
+    }
+    fn business_replicate_apply(
+        &mut self,
+        source: CanisterId,
+        change: AssetChange,
+        data: Option<Vec<u8>>,
+    ) -> Result<(), String> {
+        self.replicate_apply(source, change, data)
+    }
+    fn business_replicate_snapshot_done(
+        &mut self,
+        source: CanisterId,
+        seq: u64,
+        paths: HashSet<String>,
+    ) -> Result<(), String> {
+        self.replicate_snapshot_done(source, seq, paths)
+    }
+
+    fn business_verify_hashes(&mut self, budget: &mut usize) {
+        self.verify_hashes(budget);
+        self.refresh_manifest(); // 隔离的文件会被移除
+        self.notify_subscribers();
+    }
+    fn business_compress_files(&mut self, budget: &mut usize) {
+        self.compress_files(budget);
+    }
+    fn business_notify_subscribers(&mut self) {
+        self.notify_subscribers();
+    }
+    fn business_audit_assets(&mut self, budget: &mut usize) {
+        self.audit_assets(budget);
+        self.refresh_manifest(); // 修复时可能移除文件
+    }
+}
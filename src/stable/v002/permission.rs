@@ -0,0 +1,99 @@
+use ic_canister_kit::types::Permission;
+
+use crate::stable::ParsePermissionError;
+
+use super::super::check_permission;
+
+use super::types::{InnerState, ParsePermission};
+
+// 权限常量
+// 通用权限
+pub use super::super::v001::types::{
+    ACTION_PAUSE_QUERY, ACTION_PAUSE_REPLACE, ACTION_PERMISSION_FIND, ACTION_PERMISSION_QUERY,
+    ACTION_PERMISSION_UPDATE, ACTION_RECORD_FIND, ACTION_RECORD_MIGRATE, ACTION_SCHEDULE_FIND,
+    ACTION_SCHEDULE_REPLACE, ACTION_SCHEDULE_TRIGGER,
+};
+
+// 业务权限
+pub use super::super::v001::types::{
+    ACTION_BUSINESS_DELETE, ACTION_BUSINESS_QUERY, ACTION_BUSINESS_UPLOAD,
+};
+pub const ACTION_BUSINESS_SETTINGS: &str = "BusinessSettings"; // 业务设置权限
+pub const ACTION_BUSINESS_CANISTER_READ: &str = "BusinessCanisterRead"; // 其他罐子读取权限
+
+// 所有权限列表
+#[allow(unused)]
+pub const ACTIONS: [&str; 15] = [
+    // 通用权限
+    ACTION_PAUSE_QUERY,
+    ACTION_PAUSE_REPLACE,
+    ACTION_PERMISSION_QUERY,
+    ACTION_PERMISSION_FIND,
+    ACTION_PERMISSION_UPDATE,
+    ACTION_RECORD_FIND,
+    ACTION_RECORD_MIGRATE,
+    ACTION_SCHEDULE_FIND,
+    ACTION_SCHEDULE_REPLACE,
+    ACTION_SCHEDULE_TRIGGER,
+    // 业务权限
+    ACTION_BUSINESS_QUERY,
+    ACTION_BUSINESS_UPLOAD,
+    ACTION_BUSINESS_DELETE,
+    ACTION_BUSINESS_SETTINGS,
+    ACTION_BUSINESS_CANISTER_READ,
+];
+
+// 权限默认状态
+impl ParsePermission for InnerState {
+    fn parse_permission<'a>(&self, name: &'a str) -> Result<Permission, ParsePermissionError<'a>> {
+        Ok(match name {
+            // 通用权限
+            ACTION_PAUSE_QUERY => Permission::by_forbid(name),
+            ACTION_PAUSE_REPLACE => Permission::by_permit(name),
+            ACTION_PERMISSION_QUERY => Permission::by_forbid(name),
+            ACTION_PERMISSION_FIND => Permission::by_permit(name),
+            ACTION_PERMISSION_UPDATE => Permission::by_permit(name),
+            ACTION_RECORD_FIND => Permission::by_permit(name),
+            ACTION_RECORD_MIGRATE => Permission::by_permit(name),
+            ACTION_SCHEDULE_FIND => Permission::by_permit(name),
+            ACTION_SCHEDULE_REPLACE => Permission::by_permit(name),
+            ACTION_SCHEDULE_TRIGGER => Permission::by_permit(name),
+            // 业务权限
+            ACTION_BUSINESS_QUERY => Permission::by_forbid(name),
+            ACTION_BUSINESS_UPLOAD => Permission::by_permit(name),
+            ACTION_BUSINESS_DELETE => Permission::by_permit(name),
+            ACTION_BUSINESS_SETTINGS => Permission::by_permit(name),
+            ACTION_BUSINESS_CANISTER_READ => Permission::by_permit(name),
+            // 其他错误
+            _ => return Err(ParsePermissionError(name)),
+        })
+    }
+}
+
+// 通用权限
+#[allow(unused)]
+pub use super::super::v001::types::{
+    has_pause_query, has_pause_replace, has_permission_find, has_permission_query,
+    has_permission_update, has_record_find, has_record_migrate, has_schedule_find,
+    has_schedule_replace, has_schedule_trigger,
+};
+
+// 业务权限
+#[allow(unused)]
+pub use super::super::v001::types::{has_business_delete, has_business_query, has_business_upload};
+
+#[allow(unused)]
+pub fn has_business_settings() -> Result<(), String> {
+    check_permission(ACTION_BUSINESS_SETTINGS, true)
+}
+
+// 只允许被授权的罐子调用, 用户身份请使用 business_download 等接口
+#[allow(unused)]
+pub fn has_business_canister_read() -> Result<(), String> {
+    let caller = ic_canister_kit::identity::caller();
+    let bytes = caller.as_slice();
+    if bytes.len() != 10 || bytes[9] != 0x01 {
+        return Err(format!("Caller {} is not a canister", caller.to_text()));
+    }
+    check_permission(ACTION_BUSINESS_CANISTER_READ, false)
+}
@@ -0,0 +1,198 @@
+use ic_canister_kit::{common::option::display_option_by, times::now};
+
+use super::super::*;
+#[allow(unused)]
+use super::types::*;
+
+#[allow(unused)]
+#[allow(unused_variables)]
+pub async fn schedule_task(record_by: Option<CallerId>) {
+    // * 记录
+    let record_id = with_record_push(
+        super::types::RecordTopics::Schedule.topic(),
+        String::with_capacity(0),
+    );
+
+    // 如果有定时任务
+    ic_cdk::println!(
+        "{}: do schedule task... ({})",
+        display_option_by(&record_by, |p| p.to_text()),
+        now()
+    );
+
+    // ! 为了保证记录的完整性，不应当发生 panic
+    inner_task().await;
+
+    // * 记录
+    with_record_update_done(record_id);
+}
+
+async fn inner_task() {
+    // 校验, 压缩, 审计在同一次调用中执行, 共用处理量, 按顺序使用剩余的处理量
+    let mut budget = TASK_BYTES_BUDGET;
+
+    // 1. 校验上传的 hash
+    with_mut_state_without_record(|s| s.business_verify_hashes(&mut budget));
+
+    // 2. 压缩文本文件
+    with_mut_state_without_record(|s| s.business_compress_files(&mut budget));
+
+    // 3. 审计数据完整性
+    with_mut_state_without_record(|s| s.business_audit_assets(&mut budget));
+
+    // 4. 从源罐子同步文件
+    replicate().await;
+
+    // 5. 通知订阅者, 包括重试失败的通知
+    with_mut_state_without_record(|s| s.business_notify_subscribers());
+}
+
+async fn replicate() {
+    let (source, cursor, batch) =
+        match with_mut_state_without_record(|s| s.business_replicate_begin()) {
+            Some(begin) => begin,
+            None => return, // 没有设置源罐子或者正在同步
+        };
+
+    let result = pull_changes(source, cursor, batch).await;
+
+    with_mut_state_without_record(|s| s.business_replicate_end(source, result.err()));
+}
+
+// 拉取并应用源罐子的变更, 变更已被压缩或者数据已经不存在时从快照同步
+async fn pull_changes(source: CanisterId, cursor: u64, batch: u32) -> Result<(), String> {
+    let (result,): (Result<QueryChanges, ChangesError>,) =
+        ic_cdk::call(source, "business_canister_changes_since", (cursor, batch))
+            .await
+            .map_err(|(code, message)| format!("pull changes failed: {code:?} {message}"))?;
+    let changes = match result {
+        Ok(changes) => changes,
+        Err(ChangesError::Compacted { .. } | ChangesError::Unknown { .. }) => {
+            return pull_snapshot(source, batch).await;
+        }
+    };
+
+    with_mut_state_without_record(|s| s.business_replicate_latest(source, changes.latest));
+    let superseded = superseded_changes(&changes.changes);
+    for change in changes.changes {
+        if superseded.contains(&change.seq) {
+            with_mut_state_without_record(|s| s.business_replicate_skip(source, change.seq))?;
+            continue;
+        }
+        let data = match pull_asset_if_missing(source, &change.op, change.new_hash).await? {
+            Pulled::Exists => None,
+            Pulled::Data(data) => Some(data),
+            Pulled::Missing => return pull_snapshot(source, batch).await, // 数据已经被后面的变更替代了
+        };
+        with_mut_state_without_record(|s| s.business_replicate_apply(source, change, data))?;
+    }
+    Ok(())
+}
+
+// 分页拉取快照, 使用第一页的序号, 之后的变更再从变更记录中拉取
+async fn pull_snapshot(source: CanisterId, batch: u32) -> Result<(), String> {
+    let mut seq = None;
+    let mut after: Option<String> = None;
+    let mut paths = HashSet::new();
+    loop {
+        let (snapshot,): (QueryChangesSnapshot,) = ic_cdk::call(
+            source,
+            "business_canister_changes_snapshot",
+            (after.clone(), batch),
+        )
+        .await
+        .map_err(|(code, message)| format!("pull snapshot failed: {code:?} {message}"))?;
+        let seq = *seq.get_or_insert(snapshot.seq);
+
+        for file in snapshot.files {
+            // 文件本身和各种编码数据都当作变更应用
+            let mut changes = vec![(ChangeOp::Put, file.hash)];
+            for encoding in file.encodings {
+                changes.push((ChangeOp::PutEncoding(encoding.encoding), encoding.hash));
+            }
+            for (op, hash) in changes {
+                let hash =
+                    HashDigest::from_hex(&hash).ok_or_else(|| format!("wrong hash: {hash}"))?;
+                let data = match pull_asset_if_missing(source, &op, Some(hash)).await? {
+                    Pulled::Exists => None,
+                    Pulled::Data(data) => Some(data),
+                    Pulled::Missing => break, // 文件在快照之后变化了, 之后的变更会再次同步
+                };
+                let change = AssetChange {
+                    seq: 0,
+                    path: file.path.clone(),
+                    op,
+                    old_hash: None,
+                    new_hash: Some(hash),
+                    headers: file.headers.clone(),
+                    timestamp: file.modified,
+                    caller: source,
+                };
+                with_mut_state_without_record(|s| {
+                    s.business_replicate_apply(source, change, data)
+                })?;
+                paths.insert(file.path.clone());
+            }
+        }
+
+        after = snapshot.next;
+        if after.is_none() {
+            return with_mut_state_without_record(|s| {
+                s.business_replicate_snapshot_done(source, seq, paths)
+            });
+        }
+    }
+}
+
+// 拉取数据的结果
+enum Pulled {
+    Exists,        // 本地已经有该数据了
+    Data(Vec<u8>), // 拉取到的数据
+    Missing,       // 源罐子已经没有该数据了
+}
+
+// 本地没有的数据需要分块拉取
+async fn pull_asset_if_missing(
+    source: CanisterId,
+    op: &ChangeOp,
+    hash: Option<HashDigest>,
+) -> Result<Pulled, String> {
+    let hash = match (op, hash) {
+        (ChangeOp::Delete | ChangeOp::DeleteEncoding(_), _) | (_, None) => {
+            return Ok(Pulled::Exists)
+        }
+        (_, Some(hash)) => hash,
+    };
+    if with_state(|s| s.business_assets_get(&hash).is_some()) {
+        return Ok(Pulled::Exists);
+    }
+
+    let mut data = Vec::new();
+    for index in 0.. {
+        let arg = DownloadingHashArg {
+            hash,
+            chunk_size: MAX_DOWNLOAD_CHUNK_SIZE,
+            index,
+        };
+        let (result,): (Result<Vec<u8>, DownloadingError>,) = ic_cdk::call(
+            source,
+            "business_canister_download_chunk_by_hash_certified",
+            (arg,),
+        )
+        .await
+        .map_err(|(code, message)| format!("pull asset failed: {code:?} {message}"))?;
+        match result {
+            Ok(chunk) => {
+                let last = chunk.len() < MAX_DOWNLOAD_CHUNK_SIZE as usize;
+                data.extend(chunk);
+                if last {
+                    break;
+                }
+            }
+            Err(DownloadingError::IndexOutOfRange { .. }) => break, // 大小刚好是块的整数倍
+            Err(DownloadingError::FileNotFound(_)) => return Ok(Pulled::Missing),
+            Err(e) => return Err(format!("pull asset failed: {e}")),
+        }
+    }
+    Ok(Pulled::Data(data))
+}
@@ -0,0 +1,3489 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, EnumString};
+
+pub use ic_canister_kit::types::*;
+
+#[allow(unused)]
+pub use super::super::{Business, ParsePermission, ScheduleTask};
+
+#[allow(unused)]
+pub use super::super::business::*;
+#[allow(unused)]
+pub use super::business::*;
+#[allow(unused)]
+pub use super::permission::*;
+#[allow(unused)]
+pub use super::schedule::schedule_task;
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, EnumIter, EnumString, strum_macros::Display)]
+pub enum RecordTopics {
+    // ! 新的权限类型从 0 开始
+    UploadFile = 0, // 上传文件
+    DeleteFile = 1, // 删除文件
+    Security = 2,   // 安全事件
+    Settings = 3,   // 业务设置
+    Notify = 4,     // 通知订阅者
+
+    // ! 系统倒序排列
+    CyclesCharge = 249, // 充值
+    Upgrade = 250,      // 升级
+    Schedule = 251,     // 定时任务
+    Record = 252,       // 记录
+    Permission = 253,   // 权限
+    Pause = 254,        // 维护
+    Initial = 255,      // 初始化
+}
+#[allow(unused)]
+impl RecordTopics {
+    pub fn topic(&self) -> RecordTopic {
+        *self as u8
+    }
+    pub fn topics() -> Vec<String> {
+        RecordTopics::iter().map(|x| x.to_string()).collect()
+    }
+    pub fn from(topic: &str) -> Result<Self, strum::ParseError> {
+        RecordTopics::from_str(topic)
+    }
+}
+
+// 框架需要的数据结构
+#[derive(Serialize, Deserialize, Default)]
+pub struct CanisterKit {
+    pub pause: Pause,             // 记录维护状态 // ? 堆内存 序列化
+    pub permissions: Permissions, // 记录自身权限 // ? 堆内存 序列化
+    pub records: Records,         // 记录操作记录 // ? 堆内存 序列化
+    pub schedule: Schedule,       // 记录定时任务 // ? 堆内存 序列化
+}
+
+// 能序列化的和不能序列化的放在一起
+// 其中不能序列化的采用如下注解
+// #[serde(skip)] 默认初始化方式
+// #[serde(skip, default="init_xxx")] 指定初始化方式
+// ! 如果使用 ic-stable-structures 提供的稳定内存，不能变更 memory_id 的使用类型，否则会出现各个版本不兼容，数据会被清空
+#[derive(Serialize, Deserialize)]
+pub struct InnerState {
+    pub canister_kit: CanisterKit, // 框架需要的数据 // ? 堆内存 序列化
+
+    // 业务数据
+    pub hashed: bool, // 是否相信上传的 hash 值，true -> 直接采用接口传递的 hash 值， false -> 数据上传完成后，需要罐子再 hash 一次 // ? 堆内存 序列化
+    pub verify: bool, // hashed 为 true 时，是否需要在定时任务中再次校验 hash，校验失败的数据会被隔离 // ? 堆内存 序列化
+
+    pub assets: HashMap<HashDigest, AssetData>, // key 是 hash // ? 堆内存 序列化
+    pub files: HashMap<String, AssetFile>,      // key 是 path // ? 堆内存 序列化
+    pub hashes: HashMap<HashDigest, HashedPath>, // key 是 hash, value 是 path, 没有 path 的数据是没有保存意义的 // ? 堆内存 序列化
+
+    pub uploading: HashMap<String, UploadingFile>, // key 是 path // ? 堆内存 序列化
+
+    unverified: HashSet<HashDigest>, // 等待校验的 hash // ? 堆内存 序列化
+    #[serde(default)]
+    claimed: HashSet<HashDigest>, // 没有开启校验时保存的声称的 hash, 不能当作可信的内容 // ? 堆内存 序列化
+    #[serde(skip)]
+    verifying: Option<HashVerifying>, // 正在校验的 hash, 升级后重新校验即可 // ? 堆内存 不序列化
+    quarantined: HashMap<HashDigest, QuarantinedAsset>, // key 是声称的 hash // ? 堆内存 序列化
+
+    #[serde(default)]
+    compress_queue: VecDeque<CompressingFile>, // 等待压缩的文件, 完成后才移除 // ? 堆内存 序列化
+    #[serde(skip)]
+    compressing: Option<Compressing>, // 正在压缩的文件, 升级后重新压缩即可 // ? 堆内存 不序列化
+
+    #[serde(default)]
+    pub routing: RoutingSettings, // http 路由设置 // ? 堆内存 序列化
+    #[serde(default)]
+    pub error_pages: Vec<ErrorPage>, // 错误响应使用的页面 // ? 堆内存 序列化
+    #[serde(default)]
+    pub maintenance: MaintenanceSettings, // 维护状态时的 http 响应 // ? 堆内存 序列化
+    #[serde(default)]
+    pub route_rules: Vec<RouteRule>, // 重定向和重写规则, 按顺序匹配 // ? 堆内存 序列化
+    #[serde(default)]
+    pub header_rules: Vec<HeaderRule>, // 按路径添加的响应头 // ? 堆内存 序列化
+    #[serde(skip)]
+    globs: HashMap<String, regex::Regex>, // 编译好的通配符, 规则变化后需要重新编译 // ? 堆内存 不序列化
+    #[serde(default)]
+    pub cors_rules: Vec<CorsRule>, // 按路径前缀的跨域设置 // ? 堆内存 序列化
+    #[serde(default)]
+    pub explore: ExploreSettings, // 根路径和浏览页面的设置 // ? 堆内存 序列化
+    #[serde(default)]
+    pub manifest: ManifestSettings, // 资源清单的设置 // ? 堆内存 序列化
+    #[serde(skip)]
+    manifest_cache: Option<Vec<u8>>, // 生成好的资源清单, 文件变化后需要重新生成 // ? 堆内存 不序列化
+
+    #[serde(default)]
+    changes: ChangeLog, // 文件变更记录, 用于同步镜像 // ? 堆内存 序列化
+    #[serde(default)]
+    replication: Replication, // 从源罐子同步文件 // ? 堆内存 序列化
+    #[serde(default)]
+    pub subscriptions: Vec<Subscription>, // 文件变化时需要通知的罐子 // ? 堆内存 序列化
+    #[serde(default)]
+    notifier: Notifier, // 等待发送的通知 // ? 堆内存 序列化
+
+    #[serde(default)]
+    pub audit: AuditSettings, // 完整性审计的设置 // ? 堆内存 序列化
+    #[serde(default)]
+    audit_report: Option<AuditReport>, // 最近一次完整的审计结果 // ? 堆内存 序列化
+    #[serde(skip)]
+    auditing: Option<Auditing>, // 正在进行的审计, 升级后重新开始即可 // ? 堆内存 不序列化
+
+    #[serde(default)]
+    pub metrics: MetricsSettings, // 监控指标的设置 // ? 堆内存 序列化
+    #[serde(default)]
+    counters: MetricsCounters, // 累计的事件数量 // ? 堆内存 序列化
+}
+
+impl Default for InnerState {
+    fn default() -> Self {
+        ic_cdk::println!("InnerState::default()");
+        Self {
+            canister_kit: Default::default(),
+
+            // 业务数据
+            hashed: Default::default(),
+            verify: Default::default(),
+
+            assets: Default::default(),
+            files: Default::default(),
+            hashes: Default::default(),
+
+            uploading: Default::default(),
+
+            unverified: Default::default(),
+            claimed: Default::default(),
+            verifying: Default::default(),
+            quarantined: Default::default(),
+
+            compress_queue: Default::default(),
+            compressing: Default::default(),
+
+            routing: Default::default(),
+            error_pages: Default::default(),
+            maintenance: Default::default(),
+            route_rules: Default::default(),
+            header_rules: Default::default(),
+            globs: Default::default(),
+            cors_rules: Default::default(),
+            explore: Default::default(),
+            manifest: Default::default(),
+            manifest_cache: Default::default(),
+
+            changes: Default::default(),
+            replication: Default::default(),
+            subscriptions: Default::default(),
+            notifier: Default::default(),
+
+            audit: Default::default(),
+            audit_report: Default::default(),
+            auditing: Default::default(),
+
+            metrics: Default::default(),
+            counters: Default::default(),
+        }
+    }
+}
+
+use candid::CandidType;
+
+use crate::gzip::{compressible, GzipEncoder};
+use crate::mime::{infer_content_type, MAGIC_HEAD_SIZE};
+
+// ============================== 文件数据 ==============================
+
+#[derive(
+    CandidType, Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub struct HashDigest(pub [u8; 32]);
+
+impl HashDigest {
+    pub fn hex(&self) -> String {
+        hex::encode(self.0)
+    }
+    pub fn from_hex(text: &str) -> Option<Self> {
+        let mut hash = [0; 32];
+        hex::decode_to_slice(text, &mut hash).ok()?;
+        Some(Self(hash))
+    }
+}
+
+mod assets {
+    use candid::CandidType;
+    use serde::{Deserialize, Serialize};
+
+    use super::HashDigest;
+
+    // 单个文件数据
+    #[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+    pub struct AssetData {
+        data: Vec<u8>, // 实际数据
+    }
+
+    impl AssetData {
+        pub fn from(_hash: &HashDigest, data: Vec<u8>) -> Self {
+            Self { data }
+        }
+        pub fn size(&self) -> usize {
+            self.data.len()
+        }
+        // ! 超出范围的部分直接截断, 不再报错, 起始位置超出时返回空数据
+        // ! 需要报错的旧接口 (download_by) 调用前自行检查范围
+        pub fn slice(
+            &self,
+            _hash: &HashDigest,
+            data_size: u64,
+            offset: usize,
+            size: usize,
+        ) -> std::borrow::Cow<'_, [u8]> {
+            let data_size = (data_size as usize).min(self.data.len());
+            let offset = offset.min(data_size);
+            let offset_end = offset.saturating_add(size).min(data_size);
+            std::borrow::Cow::Borrowed(&self.data[offset..offset_end])
+        }
+    }
+}
+
+pub use assets::AssetData;
+
+// 对外的路径数据 指向文件数据
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct AssetFile {
+    pub path: String,
+    pub created: TimestampNanos,
+    pub modified: TimestampNanos,
+    pub headers: Vec<(String, String)>,
+    pub hash: HashDigest,
+    pub size: u64,
+    #[serde(default)]
+    pub encodings: HashMap<String, AssetEncoding>, // 同一路径的其他编码数据, key 是 Content-Encoding, 如 gzip br
+}
+
+// 文件的某种编码数据
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct AssetEncoding {
+    pub hash: HashDigest,
+    pub size: u64,
+    pub modified: TimestampNanos,
+}
+
+// 没有编码的数据就是文件本身
+pub const IDENTITY_ENCODING: &str = "identity";
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HashedPath(pub HashSet<String>);
+
+// =========== 校验过程中的对象 ===========
+
+// 分段校验的进度, 数据太大时需要多次定时任务才能完成
+pub struct HashVerifying {
+    pub hash: HashDigest, // 声称的 hash
+    pub offset: usize,    // 已经校验到的位置
+    hasher: sha2::Sha256, // 计算中的 hash
+}
+
+impl HashVerifying {
+    fn new(hash: HashDigest) -> Self {
+        use sha2::Digest;
+        Self {
+            hash,
+            offset: 0,
+            hasher: sha2::Sha256::new(),
+        }
+    }
+}
+
+// 校验失败被隔离的数据
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QuarantinedAsset {
+    pub hash: HashDigest,            // 上传时声称的 hash
+    pub actual: HashDigest,          // 实际计算得到的 hash
+    pub data: AssetData,             // 被隔离的数据, 不再对外提供
+    pub files: Vec<AssetFile>,       // 原来指向该数据的文件
+    pub quarantined: TimestampNanos, // 隔离时间
+}
+
+// 一次定时任务中校验, 压缩, 审计共用的数据处理量, 避免超过单次调用的指令上限
+pub const TASK_BYTES_BUDGET: usize = 1024 * 1024 * 128;
+
+// =========== 压缩过程中的对象 ===========
+
+// 等待压缩的文件
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompressingFile {
+    pub path: String,
+    pub hash: HashDigest, // 压缩的是这个 hash 的数据, 文件变化了就不用压缩了
+}
+
+// 分段压缩的进度, 数据太大时需要多次定时任务才能完成
+pub struct Compressing {
+    pub file: CompressingFile,
+    pub offset: usize, // 已经压缩到的位置
+    gzip: GzipEncoder, // 压缩中的数据
+}
+
+// 压缩使用的编码
+pub const GZIP_ENCODING: &str = "gzip";
+// 太小的文件不需要压缩
+const COMPRESS_MIN_SIZE: u64 = 1024;
+// 压缩消耗的指令数大约是 hash 的倍数, 按倍数扣除处理量
+const COMPRESS_COST: usize = 8;
+
+// =========== 上传过程中的对象 ===========
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct UploadingFile {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub hash: HashDigest, // hash 值，在 hashed 为 false 的情况下不使用
+    pub data: Vec<u8>,    // 上传中的数据
+
+    pub size: u64,          // 文件大小
+    pub chunk_size: u32,    // 块大小 块分割的大小
+    pub chunks: u32,        // 需要上传的次数
+    pub chunked: Vec<bool>, // 记录每一个块的上传状态
+    #[serde(default)]
+    pub chunk_hashes: Vec<Option<HashDigest>>, // 记录每一个已上传块的 hash
+    #[serde(default)]
+    pub encoding: Option<String>, // 上传的是已有文件的某种编码数据
+}
+
+// 上传参数
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct UploadingArg {
+    pub path: String,
+    pub headers: Vec<(String, String)>, // 使用的 header
+    pub hash: HashDigest,               // hash 值，在 hashed 为 false 的情况下不使用
+    pub size: u64,                      // 文件大小
+    pub chunk_size: u32,                // 块大小 块分割的大小
+    pub index: u32,                     // 本次上传的数据
+    pub chunk: Vec<u8>,                 // 上传中的数据
+    pub chunk_hash: Option<HashDigest>, // 本次上传数据的 hash 值，有则必须一致
+    pub encoding: Option<String>,       // 上传已有文件的某种编码数据, 如 gzip br, 没有则是文件本身
+}
+
+// 上传错误
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub enum UploadingError {
+    FileNotFound(String),
+    WrongChunkHash {
+        path: String,
+        index: u32,
+        expected: String,
+        actual: String,
+    },
+}
+impl Display for UploadingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadingError::FileNotFound(path) => write!(f, "file not found: {path}"),
+            UploadingError::WrongChunkHash {
+                path,
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "wrong chunk hash: {path} index: {index} expected: {expected} actual: {actual}"
+            ),
+        }
+    }
+}
+impl std::error::Error for UploadingError {}
+
+// =========== 分块下载 ===========
+
+// 推荐的下载块大小, 需要小于响应的大小限制
+pub const DOWNLOAD_CHUNK_SIZE: u32 = 1024 * 1024 * 3 / 2;
+// 允许的最大下载块大小
+pub const MAX_DOWNLOAD_CHUNK_SIZE: u32 = 1024 * 1024 * 2 - 1024 * 64;
+
+// 开始下载时返回的文件信息
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryDownloading {
+    pub path: String,
+    pub encoding: Option<String>,
+    pub hash: HashDigest, // 下载每一块时需要带上, 文件改变后下载失败
+    pub size: u64,
+    pub chunk_size: u32, // 推荐的块大小
+    pub chunks: u32,
+    pub modified: TimestampNanos,
+}
+
+// 下载参数
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct DownloadingArg {
+    pub path: String,
+    pub encoding: Option<String>, // 下载已有文件的某种编码数据, 没有则是文件本身
+    pub hash: HashDigest,         // 开始下载时得到的 hash
+    pub chunk_size: u32,          // 块大小
+    pub index: u32,               // 本次下载的块
+}
+
+// 根据 hash 下载的参数
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct DownloadingHashArg {
+    pub hash: HashDigest,
+    pub chunk_size: u32, // 块大小
+    pub index: u32,      // 本次下载的块
+}
+
+// 下载错误
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub enum DownloadingError {
+    FileNotFound(String),
+    EncodingNotFound {
+        path: String,
+        encoding: String,
+    },
+    WrongHash {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+    WrongChunkSize {
+        chunk_size: u32,
+        max: u32,
+    },
+    IndexOutOfRange {
+        path: String,
+        index: u32,
+        chunks: u32,
+    },
+}
+impl Display for DownloadingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadingError::FileNotFound(path) => write!(f, "file not found: {path}"),
+            DownloadingError::EncodingNotFound { path, encoding } => {
+                write!(f, "encoding not found: {path} encoding: {encoding}")
+            }
+            DownloadingError::WrongHash {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "wrong hash: {path} expected: {expected} actual: {actual}"
+            ),
+            DownloadingError::WrongChunkSize { chunk_size, max } => {
+                write!(f, "wrong chunk size: {chunk_size} max: {max}")
+            }
+            DownloadingError::IndexOutOfRange {
+                path,
+                index,
+                chunks,
+            } => write!(
+                f,
+                "index out of range: {path} index: {index} chunks: {chunks}"
+            ),
+        }
+    }
+}
+impl std::error::Error for DownloadingError {}
+
+// =========== 查询的对象 ===========
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryFile {
+    pub path: String,
+    pub size: u64,
+    pub headers: Vec<(String, String)>,
+    pub created: TimestampNanos,
+    pub modified: TimestampNanos,
+    pub hash: String,
+    pub encodings: Vec<QueryEncoding>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryEncoding {
+    pub encoding: String,
+    pub size: u64,
+    pub hash: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryUploading {
+    pub path: String,
+    pub size: u64,
+    pub chunk_size: u32,
+    pub chunks: u32,
+    pub chunked: Vec<bool>,
+    pub chunk_hashes: Vec<Option<String>>, // 已上传块的 hash, 续传时可以比较本地数据
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryQuarantined {
+    pub hash: String,
+    pub actual: String,
+    pub size: u64,
+    pub paths: Vec<String>,
+    pub quarantined: TimestampNanos,
+}
+
+impl InnerState {
+    fn hash(file: &UploadingFile) -> HashDigest {
+        Self::hash_data(&file.data[0..(file.size as usize)])
+    }
+    fn hash_data(data: &[u8]) -> HashDigest {
+        use sha2::Digest;
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(data);
+        let digest: [u8; 32] = hasher.finalize().into();
+        HashDigest(digest)
+    }
+    fn put_file(
+        &mut self,
+        path: String,
+        headers: Vec<(String, String)>,
+        hash: HashDigest,
+        size: u64,
+    ) {
+        // 3. 插入 files: path -> hash
+        self.manifest_cache = None; // 资源清单需要重新生成
+        let now = ic_canister_kit::times::now();
+        let mut replaced = None;
+        if let Some(exist) = self.files.get_mut(&path) {
+            if exist.hash != hash {
+                // 内容改变了, 原来的数据和编码数据都不再需要
+                replaced = Some((exist.hash, std::mem::take(&mut exist.encodings)));
+            }
+            exist.modified = now;
+            exist.headers = headers;
+            exist.hash = hash;
+            exist.size = size;
+        } else {
+            self.files.insert(
+                path.clone(),
+                AssetFile {
+                    path: path.clone(),
+                    created: now,
+                    modified: now,
+                    headers,
+                    hash,
+                    size,
+                    encodings: HashMap::new(),
+                },
+            );
+        }
+
+        // 4. 插入 hashes: hash -> [path]
+        self.hashes.entry(hash).or_default();
+        if let Some(hash_path) = self.hashes.get_mut(&hash) {
+            hash_path.0.insert(path.clone());
+        }
+
+        // 5. 清除被替换的数据
+        if let Some((old, encodings)) = replaced {
+            let olds = std::iter::once(old).chain(encodings.into_values().map(|e| e.hash));
+            for old in olds.collect::<HashSet<_>>() {
+                if old != hash {
+                    self.unlink_hash(&old, &path);
+                }
+            }
+        }
+    }
+    fn put_encoding(&mut self, path: String, encoding: String, hash: HashDigest, size: u64) {
+        // 3. 插入 files: path -> encoding -> hash
+        let now = ic_canister_kit::times::now();
+        // ! 只是增加了编码数据, 文件本身的内容没有变化, 不修改 modified
+        let (old, headers) = match self.files.get_mut(&path) {
+            Some(file) => {
+                let old = file.encodings.insert(
+                    encoding.clone(),
+                    AssetEncoding {
+                        hash,
+                        size,
+                        modified: now,
+                    },
+                );
+                (old, file.headers.clone())
+            }
+            None => return,
+        };
+        self.log_change(
+            &path,
+            ChangeOp::PutEncoding(encoding),
+            old.as_ref().map(|old| old.hash),
+            Some(hash),
+            headers,
+        );
+
+        // 4. 插入 hashes: hash -> [path]
+        self.hashes.entry(hash).or_default().0.insert(path.clone());
+
+        // 5. 清除被替换的数据
+        if let Some(old) = old {
+            let referenced = self.files.get(&path).is_some_and(|file| {
+                file.hash == old.hash || file.encodings.values().any(|e| e.hash == old.hash)
+            });
+            if !referenced {
+                self.unlink_hash(&old.hash, &path);
+            }
+        }
+    }
+    fn put_assets(&mut self, file: UploadingFile) {
+        // 0. 先清空同路径的文件, 编码数据只替换对应的编码
+        let old = match file.encoding {
+            Some(_) => None,
+            None => self.remove_file(&file.path),
+        };
+        // 1. 计算 hash
+        let hash = if self.hashed {
+            file.hash // hashed true 直接使用
+        } else {
+            Self::hash(&file) // hashed false 要计算一次
+        };
+        // 2. 插入 assets: hash -> data
+        self.store_asset(hash, file.data);
+
+        // 存完毕 assets 数据了，然后要对文件建立代理索引
+        match file.encoding {
+            Some(encoding) => self.put_encoding(file.path, encoding, hash, file.size),
+            None => {
+                let mut headers = file.headers;
+                self.infer_headers(&file.path, &mut headers, &hash); // 没有内容类型的需要推断
+                self.log_change(
+                    &file.path,
+                    ChangeOp::Put,
+                    old.map(|old| old.hash),
+                    Some(hash),
+                    headers.clone(),
+                );
+                self.put_file(file.path.clone(), headers, hash, file.size);
+                self.compress_file(file.path); // 文本文件需要压缩
+            }
+        }
+    }
+    // 保存数据, 已有的数据尚未校验时, 内容正确的新数据替换原来的数据
+    fn store_asset(&mut self, hash: HashDigest, data: Vec<u8>) {
+        use std::collections::hash_map::Entry;
+        let unverified = self.unverified.contains(&hash) || self.claimed.contains(&hash);
+        match self.assets.entry(hash) {
+            Entry::Vacant(entry) => {
+                entry.insert(AssetData::from(&hash, data));
+                if self.hashed && self.verify {
+                    self.unverified.insert(hash); // 新的数据使用的是声称的 hash，需要等待校验
+                } else if self.hashed {
+                    self.claimed.insert(hash); // 不校验也要记住, 不能当作可信的内容
+                }
+            }
+            // ! 先上传的数据可能是伪造的, 不能让它占用正确的 hash
+            Entry::Occupied(mut entry) if unverified && Self::hash_data(&data) == hash => {
+                entry.insert(AssetData::from(&hash, data));
+                self.unverify(&hash); // 内容已经确认正确
+            }
+            Entry::Occupied(_) => {}
+        }
+    }
+    fn infer_headers(&self, path: &str, headers: &mut Vec<(String, String)>, hash: &HashDigest) {
+        let exists = |name: &str| headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name));
+        if exists("content-type") {
+            return; // 上传者指定的优先
+        }
+        let head = self
+            .assets
+            .get(hash)
+            .filter(|asset| 0 < asset.size())
+            .map(|asset| {
+                let size = asset.size();
+                asset
+                    .slice(hash, size as u64, 0, size.min(MAGIC_HEAD_SIZE))
+                    .to_vec()
+            })
+            .unwrap_or_default();
+        if let Some((content_type, encoding)) = infer_content_type(path, &head) {
+            let encoding = encoding.filter(|_| !exists("content-encoding"));
+            headers.push(("Content-Type".into(), content_type.into()));
+            if let Some(encoding) = encoding {
+                headers.push(("Content-Encoding".into(), encoding.into()));
+            }
+        }
+    }
+    pub fn clean_file(&mut self, path: &String) {
+        if let Some(file) = self.remove_file(path) {
+            self.log_change(path, ChangeOp::Delete, Some(file.hash), None, file.headers);
+        }
+    }
+    // 删除文件的某种编码数据, 文件本身不变
+    fn delete_encoding(&mut self, path: &str, encoding: &str) {
+        let (old, headers) = match self.files.get_mut(path) {
+            Some(file) => match file.encodings.remove(encoding) {
+                Some(old) => (old, file.headers.clone()),
+                None => return,
+            },
+            None => return,
+        };
+        self.manifest_cache = None; // 资源清单需要重新生成
+        self.log_change(
+            path,
+            ChangeOp::DeleteEncoding(encoding.to_string()),
+            Some(old.hash),
+            None,
+            headers,
+        );
+        let referenced = self.files.get(path).is_some_and(|file| {
+            file.hash == old.hash || file.encodings.values().any(|e| e.hash == old.hash)
+        });
+        if !referenced {
+            self.unlink_hash(&old.hash, path);
+        }
+    }
+    fn remove_file(&mut self, path: &String) -> Option<AssetFile> {
+        // 1. 删除文件
+        let file = self.files.remove(path)?;
+        self.manifest_cache = None; // 资源清单需要重新生成
+                                    // 2. 清除 hashes
+        self.unlink_hash(&file.hash, &file.path);
+        for encoding in file.encodings.values() {
+            self.unlink_hash(&encoding.hash, &file.path);
+        }
+        Some(file)
+    }
+    fn unlink_hash(&mut self, hash: &HashDigest, path: &str) {
+        if let Some(HashedPath(path_set)) = self.hashes.get_mut(hash) {
+            path_set.remove(path);
+            if path_set.is_empty() {
+                // 需要清空
+                self.hashes.remove(hash);
+                // 4. 清空 assets
+                self.assets.remove(hash);
+                // 5. 不再需要校验
+                self.unverify(hash);
+            }
+        }
+    }
+    // 统一编码名称, identity 就是文件本身
+    fn normalize_encoding(encoding: Option<String>) -> Option<String> {
+        encoding
+            .map(|e| e.trim().to_lowercase())
+            .filter(|e| !e.is_empty() && e != IDENTITY_ENCODING)
+    }
+    fn uploading_key(path: &str, encoding: &Option<String>) -> String {
+        match encoding {
+            Some(encoding) => format!("{path}?encoding={encoding}"), // 路径不会包含 ? 参数
+            None => path.to_string(),
+        }
+    }
+    fn query_file(path: &str, file: &AssetFile) -> QueryFile {
+        QueryFile {
+            path: path.to_string(),
+            size: file.size,
+            headers: file.headers.clone(),
+            created: file.created,
+            modified: file.modified,
+            hash: file.hash.hex(),
+            encodings: file
+                .encodings
+                .iter()
+                .map(|(encoding, e)| QueryEncoding {
+                    encoding: encoding.clone(),
+                    size: e.size,
+                    hash: e.hash.hex(),
+                })
+                .collect(),
+        }
+    }
+    pub fn files(&self) -> Vec<QueryFile> {
+        self.files
+            .iter()
+            .map(|(path, file)| Self::query_file(path, file))
+            .collect()
+    }
+    pub fn file(&self, path: &str) -> Option<QueryFile> {
+        self.files
+            .get(path)
+            .map(|file| Self::query_file(path, file))
+    }
+    pub fn file_by_hash(&self, hash: &HashDigest) -> Option<QueryFile> {
+        self.find_file_by_hash(hash)
+            .map(|file| Self::query_file(&file.path, file))
+    }
+    pub fn download(&self, path: String) -> Vec<u8> {
+        #[allow(clippy::expect_used)] // ? SAFETY
+        let file = self.files.get(&path).expect("File not found");
+        #[allow(clippy::expect_used)] // ? SAFETY
+        let asset = self.assets.get(&file.hash).expect("File not found");
+        asset
+            .slice(&file.hash, file.size, 0, file.size as usize)
+            .to_vec()
+    }
+    // ! 旧接口, 出错直接中断, 新的调用方应该使用 download_chunk_by_hash
+    pub fn download_by_hash(&self, hash: HashDigest) -> Vec<u8> {
+        assert!(
+            self.is_verified(&hash),
+            "Asset not verified, use business_canister_download_chunk_by_hash"
+        );
+        #[allow(clippy::expect_used)] // ? SAFETY
+        let asset = self.assets.get(&hash).expect("Asset not found");
+        let size = asset.size();
+        assert!(
+            size <= MAX_DOWNLOAD_CHUNK_SIZE as usize,
+            "Asset is too large, use business_canister_download_chunk_by_hash"
+        );
+        if size == 0 {
+            return vec![];
+        }
+        asset.slice(&hash, size as u64, 0, size).to_vec()
+    }
+    // 引用了该数据的文件
+    pub fn find_file_by_hash(&self, hash: &HashDigest) -> Option<&AssetFile> {
+        let HashedPath(path_set) = self.hashes.get(hash)?;
+        path_set
+            .iter()
+            .filter_map(|path| self.files.get(path))
+            .find(|file| file.hash == *hash)
+    }
+    // 数据的 hash 由罐子计算或者已经校验过了
+    pub fn is_verified(&self, hash: &HashDigest) -> bool {
+        !self.unverified.contains(hash) && !self.claimed.contains(hash)
+    }
+    // ! 旧接口, 出错直接中断, 新的调用方应该使用 download_chunk
+    pub fn download_by(&self, path: String, offset: u64, size: u64) -> Vec<u8> {
+        #[allow(clippy::expect_used)] // ? SAFETY
+        let file = self.files.get(&path).expect("File not found");
+        #[allow(clippy::expect_used)] // ? SAFETY
+        let asset = self.assets.get(&file.hash).expect("File not found");
+        // 保持原来的范围检查, 超出范围不会截断
+        assert!(offset < file.size);
+        assert!(offset.saturating_add(size) <= file.size);
+        asset
+            .slice(&file.hash, file.size, offset as usize, size as usize)
+            .to_vec()
+    }
+
+    // 找到要下载的数据
+    fn downloading_target(
+        &self,
+        path: &str,
+        encoding: &Option<String>,
+    ) -> Result<(HashDigest, u64, TimestampNanos), DownloadingError> {
+        let file = self
+            .files
+            .get(path)
+            .ok_or_else(|| DownloadingError::FileNotFound(path.to_string()))?;
+        match encoding {
+            None => Ok((file.hash, file.size, file.modified)),
+            Some(encoding) if encoding == IDENTITY_ENCODING => {
+                Ok((file.hash, file.size, file.modified))
+            }
+            Some(encoding) => {
+                let e = file.encodings.get(encoding).ok_or_else(|| {
+                    DownloadingError::EncodingNotFound {
+                        path: path.to_string(),
+                        encoding: encoding.clone(),
+                    }
+                })?;
+                Ok((e.hash, e.size, e.modified))
+            }
+        }
+    }
+    pub fn download_start(
+        &self,
+        path: String,
+        encoding: Option<String>,
+    ) -> Result<QueryDownloading, DownloadingError> {
+        let (hash, size, modified) = self.downloading_target(&path, &encoding)?;
+        let chunks = size.div_ceil(DOWNLOAD_CHUNK_SIZE as u64) as u32;
+        Ok(QueryDownloading {
+            path,
+            encoding,
+            hash,
+            size,
+            chunk_size: DOWNLOAD_CHUNK_SIZE,
+            chunks,
+            modified,
+        })
+    }
+    pub fn download_chunk(&self, arg: DownloadingArg) -> Result<Vec<u8>, DownloadingError> {
+        // 1. 检查块大小
+        Self::check_chunk_size(arg.chunk_size)?;
+        // 2. 检查文件是否改变
+        let (hash, size, _) = self.downloading_target(&arg.path, &arg.encoding)?;
+        if hash != arg.hash {
+            return Err(DownloadingError::WrongHash {
+                path: arg.path,
+                expected: arg.hash.hex(),
+                actual: hash.hex(),
+            });
+        }
+        // 3. 读取数据块
+        self.read_chunk(&arg.path, &hash, size, arg.chunk_size, arg.index)
+    }
+    pub fn download_chunk_by_hash(
+        &self,
+        arg: DownloadingHashArg,
+    ) -> Result<Vec<u8>, DownloadingError> {
+        Self::check_chunk_size(arg.chunk_size)?;
+        let name = arg.hash.hex();
+        let size = self
+            .assets
+            .get(&arg.hash)
+            .map(|asset| asset.size() as u64)
+            .ok_or_else(|| DownloadingError::FileNotFound(name.clone()))?;
+        self.read_chunk(&name, &arg.hash, size, arg.chunk_size, arg.index)
+    }
+    fn check_chunk_size(chunk_size: u32) -> Result<(), DownloadingError> {
+        if chunk_size == 0 || MAX_DOWNLOAD_CHUNK_SIZE < chunk_size {
+            return Err(DownloadingError::WrongChunkSize {
+                chunk_size,
+                max: MAX_DOWNLOAD_CHUNK_SIZE,
+            });
+        }
+        Ok(())
+    }
+    // 读取第 index 块数据, 超出范围返回错误
+    fn read_chunk(
+        &self,
+        path: &str,
+        hash: &HashDigest,
+        size: u64,
+        chunk_size: u32,
+        index: u32,
+    ) -> Result<Vec<u8>, DownloadingError> {
+        let chunks = size.div_ceil(chunk_size as u64);
+        if chunks <= index as u64 {
+            return Err(DownloadingError::IndexOutOfRange {
+                path: path.to_string(),
+                index,
+                chunks: chunks as u32,
+            });
+        }
+        let asset = self
+            .assets
+            .get(hash)
+            .ok_or_else(|| DownloadingError::FileNotFound(path.to_string()))?;
+        let offset = index as u64 * chunk_size as u64;
+        let offset_end = (offset + chunk_size as u64).min(size);
+        Ok(asset
+            .slice(hash, size, offset as usize, (offset_end - offset) as usize)
+            .to_vec())
+    }
+
+    fn chunks(arg: &UploadingArg) -> u32 {
+        let mut chunks = arg.size / arg.chunk_size as u64; // 完整的块数
+        if chunks * (arg.chunk_size as u64) < arg.size {
+            chunks += 1;
+        }
+        chunks as u32
+    }
+    fn offset(arg: &UploadingArg) -> (usize, usize) {
+        let chunks = Self::chunks(arg);
+        let offset = arg.chunk_size as u64 * arg.index as u64;
+        let mut offset_end = offset + arg.chunk_size as u64;
+        if arg.index == chunks - 1 {
+            offset_end = arg.size;
+        }
+        (offset as usize, offset_end as usize)
+    }
+    fn check_path_and_headers(arg: &UploadingArg) {
+        // 1. 检查 路径名
+        assert!(!arg.path.is_empty(), "must has path");
+        assert!(arg.path.starts_with('/'), "path must start with /");
+        // 2. 检查 headers
+        for (name, value) in &arg.headers {
+            assert!(name.len() <= 64, "header name is too large");
+            assert!(value.len() <= 1024 * 8, "header value is too large");
+        }
+    }
+    fn check_size_and_data(arg: &UploadingArg) {
+        // 3. 检查 size
+        assert!(0 < arg.size, "size can not be 0");
+        assert!(
+            arg.size <= 1024 * 1024 * 1024 * 2, // 最大文件 2G
+            "size must less than 4GB"
+        );
+        // 4. 检查 chunk_size
+        assert!(0 < arg.chunk_size, "chunk size can not be 0");
+        // 5. 检查 index
+        let chunks = Self::chunks(arg);
+        assert!(arg.index < chunks, "wrong index");
+        // 6. 检查 data
+        if arg.index < chunks - 1 || arg.size == arg.chunk_size as u64 * chunks as u64 {
+            // 是前面完整的 或者 整好整除
+            assert!(
+                arg.chunk.len() as u32 == arg.chunk_size,
+                "wrong chunk length"
+            );
+        } else {
+            // 是剩下的
+            assert!(
+                arg.chunk.len() as u64 == arg.size % (arg.chunk_size as u64),
+                "wrong chunk length"
+            );
+        }
+    }
+    fn assure_uploading(&mut self, key: &str, arg: &UploadingArg) {
+        let chunks = Self::chunks(arg);
+        if let Some(exist) = self.uploading.get(key) {
+            // 已经有这个文件了, 需要比较一下, 参数是否一致
+            assert!(exist.path == arg.path, "wrong path, system error.");
+            if exist.hash != arg.hash // hash 不一致
+                || exist.size != arg.size // 文件长度不一致
+                || exist.data.len() != arg.size as usize // 暂存长度不对
+                || exist.chunk_size != arg.chunk_size
+                || exist.chunks != chunks
+                || exist.chunked.len() != chunks as usize
+            {
+                // 非致命错误, 清空原来的缓存重新上传就好
+                self.uploading.remove(key);
+            }
+        }
+        if !self.uploading.contains_key(key) {
+            // 原来没有的情况下
+            self.uploading.insert(
+                key.to_string(),
+                UploadingFile {
+                    path: arg.path.clone(),
+                    headers: arg.headers.clone(),
+                    hash: arg.hash,
+                    data: vec![0; arg.size as usize],
+                    size: arg.size,
+                    chunk_size: arg.chunk_size,
+                    chunks,
+                    chunked: vec![false; chunks as usize],
+                    chunk_hashes: vec![None; chunks as usize],
+                    encoding: arg.encoding.clone(),
+                },
+            );
+        }
+    }
+    // 一批上传在修改任何数据之前先检查, 避免只保存了前面的部分
+    // 返回每一块数据的 hash, 保存时不需要重新计算
+    pub fn check_uploading(
+        &self,
+        args: &[UploadingArg],
+    ) -> Result<Vec<HashDigest>, UploadingError> {
+        let mut chunk_hashes = Vec::with_capacity(args.len());
+        let mut chunked: HashMap<&str, (HashDigest, u64, u32, Vec<bool>)> = HashMap::new(); // 本批次上传后的分块情况
+        let mut completed: HashSet<&str> = HashSet::new(); // 本批次中完整的文件
+        let mut stored: HashSet<HashDigest> = HashSet::new(); // 本批次中保存的可以复用的数据
+        for arg in args {
+            let actual = Self::hash_data(&arg.chunk);
+            if let Some(expected) = arg.chunk_hash {
+                if expected != actual {
+                    return Err(UploadingError::WrongChunkHash {
+                        path: arg.path.clone(),
+                        index: arg.index,
+                        expected: expected.hex(),
+                        actual: actual.hex(),
+                    });
+                }
+            }
+            chunk_hashes.push(actual);
+
+            if Self::normalize_encoding(arg.encoding.clone()).is_some() {
+                // 编码数据只能附加到已有的文件, 或者同一批中前面已经完整的文件
+                if !self.files.contains_key(&arg.path) && !completed.contains(arg.path.as_str()) {
+                    return Err(UploadingError::FileNotFound(arg.path.clone()));
+                }
+                continue;
+            }
+
+            // 和保存时一样, 已经存在的数据直接复用
+            if self.hashed
+                && ((!self.unverified.contains(&arg.hash) && self.assets.contains_key(&arg.hash))
+                    || stored.contains(&arg.hash))
+            {
+                completed.insert(&arg.path);
+                continue;
+            }
+            Self::check_size_and_data(arg);
+            let chunks = Self::chunks(arg);
+            let entry = chunked.entry(&arg.path).or_insert_with(|| {
+                // 参数一致时, 之前上传的块仍然有效
+                let done = self
+                    .uploading
+                    .get(&arg.path)
+                    .filter(|exist| {
+                        exist.hash == arg.hash
+                            && exist.size == arg.size
+                            && exist.data.len() == arg.size as usize
+                            && exist.chunk_size == arg.chunk_size
+                            && exist.chunks == chunks
+                            && exist.chunked.len() == chunks as usize
+                    })
+                    .map(|exist| exist.chunked.clone())
+                    .unwrap_or_else(|| vec![false; chunks as usize]);
+                (arg.hash, arg.size, arg.chunk_size, done)
+            });
+            if entry.0 != arg.hash || entry.1 != arg.size || entry.2 != arg.chunk_size {
+                // 参数变化了, 保存时会清空原来的缓存
+                *entry = (
+                    arg.hash,
+                    arg.size,
+                    arg.chunk_size,
+                    vec![false; chunks as usize],
+                );
+            }
+            entry.3[arg.index as usize] = true;
+            if entry.3.iter().all(|c| *c) {
+                entry.3 = vec![false; chunks as usize]; // 保存后缓存会被清除
+                completed.insert(&arg.path);
+                if self.hashed && !self.verify {
+                    stored.insert(arg.hash);
+                }
+            }
+        }
+        Ok(chunk_hashes)
+    }
+    // ! chunk_hash 是 check_uploading 已经检查过的块 hash
+    pub fn put_uploading(
+        &mut self,
+        mut arg: UploadingArg,
+        chunk_hash: HashDigest,
+    ) -> Result<(), UploadingError> {
+        // 1. 检查参数是否有效
+        Self::check_path_and_headers(&arg);
+        arg.encoding = Self::normalize_encoding(arg.encoding.take());
+        if arg.encoding.is_some() && !self.files.contains_key(&arg.path) {
+            return Err(UploadingError::FileNotFound(arg.path)); // 编码数据只能附加到已有的文件
+        }
+
+        // 2. 如果 hashed true 并且已经存在改 hash 值文件了，直接保存即可
+        // ! 尚未校验的数据不能复用，否则可能指向错误的内容
+        if self.hashed && !self.unverified.contains(&arg.hash) {
+            if let Some(asset) = self.assets.get(&arg.hash) {
+                let size = asset.size() as u64; // size 不可信，只能从已存在的文件内容中查找
+                match arg.encoding {
+                    Some(encoding) => self.put_encoding(arg.path, encoding, arg.hash, size),
+                    None => {
+                        let old = self.files.get(&arg.path).map(|file| file.hash);
+                        let mut headers = arg.headers;
+                        self.infer_headers(&arg.path, &mut headers, &arg.hash); // 没有内容类型的需要推断
+                        self.log_change(
+                            &arg.path,
+                            ChangeOp::Put,
+                            old,
+                            Some(arg.hash),
+                            headers.clone(),
+                        );
+                        self.put_file(arg.path.clone(), headers, arg.hash, size);
+                        self.compress_file(arg.path); // 文本文件需要压缩
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        // 3. 检查其他参数
+        Self::check_size_and_data(&arg);
+
+        // 4. 确保有缓存空间
+        let key = Self::uploading_key(&arg.path, &arg.encoding);
+        self.assure_uploading(&key, &arg); // 确保该文件已经存在缓存数据了
+        self.counters.upload_chunks += 1;
+        self.counters.upload_bytes += arg.chunk.len() as u64;
+
+        // 5. 找的对应的缓存文件
+        let mut done = false;
+        if let Some(file) = self.uploading.get_mut(&key) {
+            // 3. 复制有效的信息
+            let (offset, offset_end) = Self::offset(&arg);
+            file.headers = arg.headers;
+            file.data.splice(offset..offset_end, arg.chunk); // 复制内容
+            file.chunked[arg.index as usize] = true;
+            if file.chunk_hashes.len() != file.chunks as usize {
+                file.chunk_hashes = vec![None; file.chunks as usize]; // 旧数据没有记录
+            }
+            file.chunk_hashes[arg.index as usize] = Some(chunk_hash);
+
+            // 4. 是否已经完整
+            done = file.chunked.iter().all(|c| *c);
+        }
+        if done {
+            if let Some(file) = self.uploading.remove(&key) {
+                // 处理这个已经完成的数据
+                self.put_assets(file);
+            }
+        }
+        Ok(())
+    }
+    pub fn uploading(&self, path: &str, encoding: Option<String>) -> Option<QueryUploading> {
+        let key = Self::uploading_key(path, &Self::normalize_encoding(encoding));
+        self.uploading.get(&key).map(|file| QueryUploading {
+            path: file.path.clone(),
+            size: file.size,
+            chunk_size: file.chunk_size,
+            chunks: file.chunks,
+            chunked: file.chunked.clone(),
+            chunk_hashes: file
+                .chunk_hashes
+                .iter()
+                .map(|h| h.map(|h| h.hex()))
+                .collect(),
+        })
+    }
+    pub fn clean_uploading(&mut self, path: &String) {
+        self.uploading.retain(|_, file| file.path != *path); // 包括各种编码数据
+    }
+}
+
+// ============================== 校验数据 ==============================
+
+impl InnerState {
+    pub fn verify_update(&mut self, verify: bool) {
+        self.verify = verify;
+        if verify {
+            // 已有的数据都需要校验一次
+            self.unverified.extend(self.assets.keys().cloned());
+        } else {
+            let unverified = std::mem::take(&mut self.unverified);
+            self.claimed.extend(unverified); // 没有校验完成的仍然只是声称的 hash
+            self.verifying = None;
+        }
+    }
+    fn unverify(&mut self, hash: &HashDigest) {
+        self.unverified.remove(hash);
+        self.claimed.remove(hash);
+        if self.verifying.as_ref().is_some_and(|v| v.hash == *hash) {
+            self.verifying = None;
+        }
+    }
+    // 定时任务中执行, 最多校验 budget 的数据, 并扣除处理量
+    pub fn verify_hashes(&mut self, budget: &mut usize) {
+        use sha2::Digest;
+        while 0 < *budget {
+            // 1. 找到需要校验的数据
+            let mut verifying = match self.verifying.take() {
+                Some(verifying) => verifying,
+                None => match self.unverified.iter().next() {
+                    Some(hash) => HashVerifying::new(*hash),
+                    None => return, // 没有需要校验的数据了
+                },
+            };
+            let asset = match self.assets.get(&verifying.hash) {
+                Some(asset) => asset,
+                None => {
+                    self.unverify(&verifying.hash); // 数据已经不存在了
+                    continue;
+                }
+            };
+
+            // 2. 计算本次的数据
+            let size = asset.size();
+            let offset_end = size.min(verifying.offset + *budget);
+            verifying.hasher.update(asset.slice(
+                &verifying.hash,
+                size as u64,
+                verifying.offset,
+                offset_end - verifying.offset,
+            ));
+            *budget -= offset_end - verifying.offset;
+            verifying.offset = offset_end;
+            if offset_end < size {
+                self.verifying = Some(verifying); // 下次继续
+                return;
+            }
+
+            // 3. 比较结果
+            let HashVerifying { hash, hasher, .. } = verifying;
+            let actual = HashDigest(hasher.finalize().into());
+            self.unverify(&hash);
+            if actual != hash {
+                self.quarantine(hash, actual);
+            }
+        }
+    }
+    // 隔离 hash 不一致的数据
+    fn quarantine(&mut self, hash: HashDigest, actual: HashDigest) {
+        let data = match self.assets.remove(&hash) {
+            Some(data) => data,
+            None => return,
+        };
+        self.counters.quarantined += 1;
+        let mut files = vec![];
+        if let Some(HashedPath(path_set)) = self.hashes.remove(&hash) {
+            for path in path_set {
+                let file = match self.files.get_mut(&path) {
+                    Some(file) => file,
+                    None => continue,
+                };
+                if file.hash == hash {
+                    // 文件本身被隔离
+                    let file = file.clone();
+                    self.clean_file(&path);
+                    files.push(file);
+                } else {
+                    // 只是某种编码被隔离
+                    let removed = file.clone();
+                    let encodings: Vec<String> = file
+                        .encodings
+                        .iter()
+                        .filter(|(_, e)| e.hash == hash)
+                        .map(|(encoding, _)| encoding.clone())
+                        .collect();
+                    for encoding in encodings {
+                        self.delete_encoding(&path, &encoding);
+                    }
+                    files.push(removed);
+                }
+            }
+        }
+        let paths = files
+            .iter()
+            .map(|f| f.path.clone())
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.quarantined
+            .entry(hash)
+            .or_insert_with(|| QuarantinedAsset {
+                hash,
+                actual,
+                data,
+                files: vec![],
+                quarantined: ic_canister_kit::times::now(),
+            })
+            .files
+            .extend(files);
+
+        // * 记录安全事件
+        let record_id = self.record_push(
+            ic_canister_kit::identity::caller(),
+            RecordTopics::Security.topic(),
+            format!(
+                "hash mismatch: claimed {} actual {} paths: [{}]",
+                hash.hex(),
+                actual.hex(),
+                paths
+            ),
+        );
+        self.record_update(record_id, "quarantined".into());
+    }
+    pub fn quarantined(&self) -> Vec<QueryQuarantined> {
+        self.quarantined
+            .values()
+            .map(|q| QueryQuarantined {
+                hash: q.hash.hex(),
+                actual: q.actual.hex(),
+                size: q.data.size() as u64,
+                paths: q.files.iter().map(|f| f.path.clone()).collect(),
+                quarantined: q.quarantined,
+            })
+            .collect()
+    }
+    pub fn quarantine_delete(&mut self, hashes: Vec<HashDigest>) {
+        for hash in hashes {
+            self.quarantined.remove(&hash);
+        }
+    }
+}
+
+// ============================== 压缩数据 ==============================
+
+impl InnerState {
+    // 文本文件需要准备 gzip 编码
+    fn compress_file(&mut self, path: String) {
+        let file = match self.files.get(&path) {
+            Some(file) => file,
+            None => return,
+        };
+        if file.size < COMPRESS_MIN_SIZE || file.encodings.contains_key(GZIP_ENCODING) {
+            return;
+        }
+        let mut compress = false;
+        for (name, value) in file.headers.iter() {
+            match name.to_lowercase().as_str() {
+                "content-type" => compress = compressible(value),
+                "content-encoding" => return, // 已经编码过的不再压缩
+                _ => {}
+            }
+        }
+        if !compress {
+            return;
+        }
+
+        // 相同数据的文件已经压缩过了, 直接复用
+        let hash = file.hash;
+        let exist = self.hashes.get(&hash).and_then(|HashedPath(path_set)| {
+            path_set
+                .iter()
+                .filter_map(|p| self.files.get(p))
+                .filter(|f| f.hash == hash)
+                .find_map(|f| f.encodings.get(GZIP_ENCODING))
+                .map(|e| (e.hash, e.size))
+        });
+        match exist {
+            Some((gzip_hash, gzip_size)) => {
+                self.put_encoding(path, GZIP_ENCODING.into(), gzip_hash, gzip_size)
+            }
+            None => self
+                .compress_queue
+                .push_back(CompressingFile { path, hash }),
+        }
+    }
+    // 定时任务中执行, 最多压缩 budget / COMPRESS_COST 的数据, 并扣除处理量
+    pub fn compress_files(&mut self, budget: &mut usize) {
+        let mut remain = *budget / COMPRESS_COST;
+        let total = remain;
+        self.compress_within(&mut remain);
+        *budget -= (total - remain) * COMPRESS_COST;
+    }
+    fn compress_within(&mut self, remain: &mut usize) {
+        while 0 < *remain {
+            // 1. 找到需要压缩的文件
+            let mut compressing = match self.compressing.take() {
+                Some(compressing) => compressing,
+                None => match self.compress_queue.front() {
+                    Some(file) => Compressing {
+                        file: file.clone(),
+                        offset: 0,
+                        gzip: GzipEncoder::default(),
+                    },
+                    None => return, // 没有需要压缩的文件了
+                },
+            };
+            let file = &compressing.file;
+            let asset = match self
+                .files
+                .get(&file.path)
+                .filter(|f| f.hash == file.hash && !f.encodings.contains_key(GZIP_ENCODING))
+                .and_then(|_| self.assets.get(&file.hash))
+            {
+                Some(asset) => asset,
+                None => {
+                    self.compress_queue.pop_front(); // 文件已经变化了
+                    continue;
+                }
+            };
+
+            // 2. 压缩本次的数据
+            let size = asset.size();
+            let offset_end = size.min(compressing.offset + *remain);
+            let data = asset.slice(
+                &file.hash,
+                size as u64,
+                compressing.offset,
+                offset_end - compressing.offset,
+            );
+            if let Err(e) = compressing.gzip.update(&data) {
+                ic_cdk::println!("compress {} failed: {}", file.path, e);
+                self.compress_queue.pop_front(); // 压缩出错, 放弃该编码
+                continue;
+            }
+            *remain -= offset_end - compressing.offset;
+            compressing.offset = offset_end;
+            if offset_end < size {
+                self.compressing = Some(compressing); // 下次继续
+                return;
+            }
+
+            // 3. 保存压缩的数据
+            self.compress_queue.pop_front();
+            let Compressing { file, gzip, .. } = compressing;
+            let data = match gzip.finish() {
+                Ok(data) => data,
+                Err(e) => {
+                    ic_cdk::println!("compress {} failed: {}", file.path, e);
+                    continue; // 压缩出错, 放弃该编码
+                }
+            };
+            if size <= data.len() {
+                continue; // 压缩没有效果
+            }
+            let gzip_hash = Self::hash_data(&data);
+            let gzip_size = data.len() as u64;
+            self.assets
+                .entry(gzip_hash)
+                .or_insert_with(|| AssetData::from(&gzip_hash, data));
+            self.put_encoding(file.path, GZIP_ENCODING.into(), gzip_hash, gzip_size);
+        }
+    }
+}
+
+// ============================== 路由设置 ==============================
+
+// http 请求找不到对应文件时的路由方式
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+// ! 早期版本的 root_index 字段已经移除, 根路径的内容由 ExploreSettings.root 决定
+// ! 旧数据中的 root_index 字段反序列化时会被忽略
+pub struct RoutingSettings {
+    pub index_files: Vec<String>, // 目录索引文件名, 如 /docs/ -> /docs/index.html
+    pub fallback: Option<String>, // 未知路径使用的文件, 单页应用可以设置为 /index.html
+    pub fallback_html_only: bool, // 只有请求 Accept 包含 text/html 时才使用 fallback
+    #[serde(default)]
+    pub autoindex: Vec<String>, // 没有索引文件时显示目录列表的路径前缀
+}
+
+impl Default for RoutingSettings {
+    fn default() -> Self {
+        Self {
+            index_files: vec!["index.html".into()],
+            fallback: None,
+            fallback_html_only: true,
+            autoindex: vec![],
+        }
+    }
+}
+
+impl RoutingSettings {
+    pub fn autoindex_enabled(&self, path: &str) -> bool {
+        self.autoindex.iter().any(|prefix| path.starts_with(prefix))
+    }
+}
+
+impl InnerState {
+    pub fn routing_update(&mut self, routing: RoutingSettings) {
+        self.routing = RoutingSettings {
+            index_files: routing
+                .index_files
+                .into_iter()
+                .map(|name| name.trim().trim_matches('/').to_string())
+                .filter(|name| !name.is_empty())
+                .collect(),
+            ..routing
+        };
+    }
+}
+
+// 错误响应使用的页面, 响应码保持不变
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErrorPage {
+    pub prefix: String,      // 生效的路径前缀, / 表示所有路径
+    pub status: Option<u16>, // 生效的响应码, 没有表示所有的错误响应
+    pub path: String,        // 作为响应内容的文件路径, 如 /404.html
+}
+
+impl InnerState {
+    // 前缀最长的优先, 相同前缀时指定了响应码的优先
+    pub fn find_error_page(&self, path: &str, status: u16) -> Option<&ErrorPage> {
+        self.error_pages
+            .iter()
+            .filter(|page| path.starts_with(&page.prefix))
+            .filter(|page| page.status.map(|s| s == status).unwrap_or(true))
+            .max_by_key(|page| (page.prefix.len(), page.status.is_some()))
+    }
+}
+
+// 罐子维护时 http 请求的响应方式
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceSettings {
+    pub enabled: bool,                 // 维护时 http 请求返回 503
+    pub retry_after: u64,              // Retry-After 秒数
+    pub page: Option<String>,          // 维护页面的文件路径, 没有则使用 503 的错误页面
+    pub allowed_prefixes: Vec<String>, // 维护时仍然正常响应的路径前缀, 如健康检查
+}
+
+impl Default for MaintenanceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retry_after: 60,
+            page: None,
+            allowed_prefixes: vec![],
+        }
+    }
+}
+
+// ============================== 路径规则 ==============================
+
+// 路径匹配方式
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PathPattern {
+    Exact(String),     // 完全匹配
+    Prefix(String),    // 前缀匹配, 剩余的部分作为 $1
+    Glob(String),      // * 匹配一段路径, ** 匹配多段路径, ? 匹配一个字符, 依次作为 $1 $2 ...
+    Extension(String), // 扩展名匹配, 如 html 匹配所有 .html 文件
+}
+
+impl PathPattern {
+    // 匹配成功返回捕获的内容, 通配符优先使用编译好的正则
+    pub fn captures(
+        &self,
+        path: &str,
+        globs: &HashMap<String, regex::Regex>,
+    ) -> Option<Vec<String>> {
+        match self {
+            PathPattern::Exact(exact) => (exact == path).then(Vec::new),
+            PathPattern::Prefix(prefix) => {
+                path.strip_prefix(prefix.as_str()).map(|r| vec![r.into()])
+            }
+            PathPattern::Glob(glob) => {
+                let compiled;
+                let regex = match globs.get(glob) {
+                    Some(regex) => regex,
+                    None => {
+                        compiled = glob_regex(glob).ok()?; // 尚未编译
+                        &compiled
+                    }
+                };
+                let captures = regex.captures(path)?;
+                Some(
+                    captures
+                        .iter()
+                        .skip(1)
+                        .map(|c| c.map(|c| c.as_str().to_string()).unwrap_or_default())
+                        .collect(),
+                )
+            }
+            PathPattern::Extension(extension) => {
+                let name = path.rsplit('/').next().unwrap_or(path); // 文件名
+                let (_, ext) = name.rsplit_once('.')?;
+                ext.eq_ignore_ascii_case(extension.trim_start_matches('.'))
+                    .then(Vec::new)
+            }
+        }
+    }
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            PathPattern::Glob(glob) => glob_regex(glob).map(|_| ()).map_err(|e| e.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// 通配符转换成正则表达式
+fn glob_regex(glob: &str) -> Result<regex::Regex, regex::Error> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str("(.*)");
+            }
+            '*' => pattern.push_str("([^/]*)"),
+            '?' => pattern.push_str("([^/])"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    regex::Regex::new(&pattern)
+}
+
+// 用捕获的内容替换 $1 ~ $9
+// ! 只扫描一遍目标, 捕获的内容中出现的 $n 不会再被替换
+fn substitute(target: &str, captures: &[String]) -> String {
+    let mut result = String::with_capacity(target.len());
+    let mut chars = target.chars().peekable();
+    while let Some(c) = chars.next() {
+        let capture = match (c, chars.peek().and_then(|n| n.to_digit(10))) {
+            ('$', Some(n @ 1..=9)) => captures.get(n as usize - 1),
+            _ => None,
+        };
+        match capture {
+            Some(capture) => {
+                chars.next();
+                result.push_str(capture);
+            }
+            None => result.push(c), // 没有对应的捕获内容, 保持原样
+        }
+    }
+    result
+}
+
+// 重定向地址中需要重新转义的字符, 捕获的内容来自解码后的路径, 不能原样放进响应头
+const LOCATION_ENCODE_SET: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`');
+
+// 匹配后的动作
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum RouteAction {
+    Redirect { status: u16, location: String }, // 重定向, 地址可以是外部链接
+    Rewrite(String),                            // 内部重写到其他路径
+}
+
+// 重定向和重写规则
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RouteRule {
+    pub pattern: PathPattern,
+    pub action: RouteAction,
+}
+
+// 支持的重定向响应码
+const REDIRECT_STATUS: [u16; 4] = [301, 302, 307, 308];
+
+impl InnerState {
+    pub fn route_rules_update(&mut self, route_rules: Vec<RouteRule>) -> Result<(), String> {
+        for rule in route_rules.iter() {
+            rule.pattern.check()?;
+            if let RouteAction::Redirect { status, .. } = &rule.action {
+                if !REDIRECT_STATUS.contains(status) {
+                    return Err(format!("wrong redirect status: {status}"));
+                }
+            }
+        }
+        self.route_rules = route_rules;
+        self.refresh_globs();
+        Ok(())
+    }
+    // 规则变化或者升级后重新编译通配符
+    pub fn refresh_globs(&mut self) {
+        self.globs = self
+            .route_rules
+            .iter()
+            .map(|rule| &rule.pattern)
+            .chain(self.header_rules.iter().map(|rule| &rule.pattern))
+            .filter_map(|pattern| match pattern {
+                PathPattern::Glob(glob) => glob_regex(glob).ok().map(|regex| (glob.clone(), regex)),
+                _ => None,
+            })
+            .collect();
+    }
+    // 第一条匹配的规则生效, 返回替换后的动作
+    pub fn route(&self, path: &str) -> Option<RouteAction> {
+        self.route_rules.iter().find_map(|rule| {
+            let captures = rule.pattern.captures(path, &self.globs)?;
+            Some(match &rule.action {
+                RouteAction::Redirect { status, location } => {
+                    let captures: Vec<String> = captures
+                        .iter()
+                        .map(|c| {
+                            percent_encoding::utf8_percent_encode(c, LOCATION_ENCODE_SET)
+                                .to_string()
+                        })
+                        .collect();
+                    RouteAction::Redirect {
+                        status: *status,
+                        location: substitute(location, &captures),
+                    }
+                }
+                RouteAction::Rewrite(target) => RouteAction::Rewrite(substitute(target, &captures)),
+            })
+        })
+    }
+}
+
+// 按路径添加的响应头
+// 没有 overwrite 的规则, 文件自带的响应头优先; 有 overwrite 的规则, 覆盖文件自带的响应头
+// 同一类规则中, 后面的规则优先
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HeaderRule {
+    pub pattern: PathPattern,
+    pub headers: Vec<(String, String)>,
+    pub overwrite: bool,
+}
+
+impl InnerState {
+    pub fn header_rules_update(&mut self, header_rules: Vec<HeaderRule>) -> Result<(), String> {
+        for rule in header_rules.iter() {
+            rule.pattern.check()?;
+            if rule.headers.iter().any(|(name, _)| name.trim().is_empty()) {
+                return Err("header name can not be empty".into());
+            }
+        }
+        self.header_rules = header_rules;
+        self.refresh_globs();
+        Ok(())
+    }
+    pub fn find_header_rules(&self, path: &str) -> Vec<&HeaderRule> {
+        self.header_rules
+            .iter()
+            .filter(|rule| rule.pattern.captures(path, &self.globs).is_some())
+            .collect()
+    }
+}
+
+// 跨域访问设置, 前缀最长的生效
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CorsRule {
+    pub prefix: String,       // 生效的路径前缀
+    pub origins: Vec<String>, // 允许的来源, * 表示所有来源
+    pub methods: Vec<String>, // 允许的方法, 没有则是 GET, HEAD, OPTIONS
+    pub headers: Vec<String>, // 允许的请求头
+    pub max_age: Option<u64>, // 预检结果的缓存秒数
+}
+
+impl CorsRule {
+    // 返回允许的来源
+    pub fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.origins.iter().any(|o| o == "*") {
+            return Some("*".into());
+        }
+        self.origins
+            .iter()
+            .find(|o| o.eq_ignore_ascii_case(origin))
+            .map(|_| origin.to_string())
+    }
+}
+
+impl InnerState {
+    pub fn find_cors_rule(&self, path: &str) -> Option<&CorsRule> {
+        self.cors_rules
+            .iter()
+            .filter(|rule| path.starts_with(&rule.prefix))
+            .max_by_key(|rule| rule.prefix.len())
+    }
+}
+
+// 根路径显示的内容
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub enum RootPage {
+    #[default]
+    Explorer, // 浏览页面
+    Index,   // 目录索引文件, 如 /index.html
+    Nothing, // 不显示, 返回 404
+}
+
+// 浏览页面的设置
+// 浏览页面需要请求者拥有 ACTION_BUSINESS_QUERY 权限
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExploreSettings {
+    pub root: RootPage,                // 根路径显示的内容
+    pub path: Option<String>,          // 浏览页面的独立路径, 如 /__explore/
+    pub private_prefixes: Vec<String>, // 浏览页面和资源清单不显示的路径前缀
+}
+
+impl Default for ExploreSettings {
+    fn default() -> Self {
+        Self {
+            root: RootPage::Explorer,
+            path: Some("/__explore/".into()),
+            private_prefixes: vec![],
+        }
+    }
+}
+
+impl ExploreSettings {
+    // 是否浏览页面的路径
+    pub fn is_explorer(&self, path: &str) -> bool {
+        (path == "/" && self.root == RootPage::Explorer) || self.path.as_deref() == Some(path)
+    }
+    // 是否启用了浏览页面
+    pub fn enabled(&self) -> bool {
+        self.root == RootPage::Explorer || self.path.is_some()
+    }
+    // 是否可以显示
+    pub fn is_public(&self, path: &str) -> bool {
+        !self
+            .private_prefixes
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+    }
+}
+
+// ============================== 资源清单 ==============================
+
+// 资源清单的设置
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestSettings {
+    pub enabled: bool,
+    pub path: String,           // 访问路径
+    pub prefix: Option<String>, // 只包含该前缀下的文件
+}
+
+impl Default for ManifestSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false, // ! 资源清单会公开文件列表, 需要主动开启
+            path: "/.well-known/asset-manifest.json".into(),
+            prefix: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ManifestFile<'a> {
+    hash: String,
+    size: u64,
+    content_type: Option<&'a str>,
+    modified: i128, // 毫秒
+    integrity: String,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    files: std::collections::BTreeMap<&'a str, ManifestFile<'a>>,
+}
+
+impl InnerState {
+    pub fn explore_update(&mut self, explore: ExploreSettings) {
+        self.explore = explore;
+        self.manifest_cache = None; // 私有路径可能变化了
+        self.refresh_manifest();
+    }
+    pub fn manifest_update(&mut self, manifest: ManifestSettings) {
+        self.manifest = manifest;
+        self.manifest_cache = None;
+        self.refresh_manifest();
+    }
+    // 修改文件的接口结束后调用, 查询接口无法保存生成的内容
+    pub fn refresh_manifest(&mut self) {
+        if self.manifest.enabled && self.manifest_cache.is_none() {
+            self.manifest_cache = Some(self.generate_manifest());
+        }
+    }
+    pub fn get_manifest(&self) -> Option<std::borrow::Cow<'_, [u8]>> {
+        if !self.manifest.enabled {
+            return None;
+        }
+        Some(match &self.manifest_cache {
+            Some(cache) => std::borrow::Cow::Borrowed(cache),
+            None => std::borrow::Cow::Owned(self.generate_manifest()),
+        })
+    }
+    fn generate_manifest(&self) -> Vec<u8> {
+        use base64::Engine;
+        let prefix = self.manifest.prefix.as_deref().unwrap_or("/");
+        let files = self
+            .files
+            .iter()
+            .filter(|(path, _)| path.starts_with(prefix))
+            .filter(|(path, _)| self.explore.is_public(path)) // 不公开私有路径
+            .map(|(path, file)| {
+                let content_type = file
+                    .headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                    .map(|(_, value)| value.as_str())
+                    .or_else(|| {
+                        infer_content_type(path, &[]).map(|(content_type, _)| content_type)
+                    });
+                let file = ManifestFile {
+                    hash: file.hash.hex(),
+                    size: file.size,
+                    content_type,
+                    modified: file.modified.into_inner() / 1000000,
+                    integrity: format!(
+                        "sha256-{}",
+                        base64::engine::general_purpose::STANDARD.encode(file.hash.0)
+                    ),
+                };
+                (path.as_str(), file)
+            })
+            .collect();
+        serde_json::to_vec(&Manifest { files }).unwrap_or_default()
+    }
+}
+
+// ============================== 变更记录 ==============================
+
+// 变更类型
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ChangeOp {
+    Put,                    // 新增或者替换文件
+    PutEncoding(String),    // 新增或者替换文件的某种编码数据
+    Delete,                 // 删除文件
+    DeleteEncoding(String), // 删除文件的某种编码数据
+}
+
+// 单条变更
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct AssetChange {
+    pub seq: u64, // 从 1 开始连续递增
+    pub path: String,
+    pub op: ChangeOp,
+    pub old_hash: Option<HashDigest>,
+    pub new_hash: Option<HashDigest>,
+    pub headers: Vec<(String, String)>,
+    pub timestamp: TimestampNanos,
+    pub caller: UserId,
+}
+
+// 变更记录, 超出保留数量的旧记录会被压缩, 只留下游标
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChangeLog {
+    seq: u64,       // 最新的序号
+    compacted: u64, // 该序号及之前的变更已被压缩, 需要从快照同步
+    #[serde(default = "default_change_retention")]
+    retention: u64, // 保留的变更数量
+    changes: VecDeque<AssetChange>,
+}
+
+fn default_change_retention() -> u64 {
+    10000
+}
+
+impl Default for ChangeLog {
+    fn default() -> Self {
+        Self {
+            seq: 0,
+            compacted: 0,
+            retention: default_change_retention(),
+            changes: VecDeque::new(),
+        }
+    }
+}
+
+impl ChangeLog {
+    fn push(&mut self, mut change: AssetChange) {
+        self.seq += 1;
+        change.seq = self.seq;
+        self.changes.push_back(change);
+        self.compact();
+    }
+    fn compact(&mut self) {
+        while self.retention < self.changes.len() as u64 {
+            if let Some(change) = self.changes.pop_front() {
+                self.compacted = change.seq;
+            }
+        }
+    }
+    // 查询 since 之后的变更, since 是上次得到的最后一个序号, 从头开始是 0
+    fn since(&self, since: u64, limit: u32) -> Result<QueryChanges, ChangesError> {
+        if since < self.compacted {
+            return Err(ChangesError::Compacted {
+                since,
+                compacted: self.compacted,
+            });
+        }
+        if self.seq < since {
+            return Err(ChangesError::Unknown {
+                since,
+                latest: self.seq,
+            });
+        }
+        let start = self.changes.partition_point(|change| change.seq <= since);
+        let changes = self
+            .changes
+            .range(start..)
+            .take(limit.clamp(1, MAX_CHANGES_LIMIT) as usize)
+            .cloned()
+            .collect();
+        Ok(QueryChanges {
+            changes,
+            latest: self.seq,
+        })
+    }
+}
+
+// 单次查询的最大变更数量
+const MAX_CHANGES_LIMIT: u32 = 1000;
+
+// 查询到的变更
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryChanges {
+    pub changes: Vec<AssetChange>,
+    pub latest: u64, // 当前最新的序号
+}
+
+// 快照, 变更已被压缩时, 从这里开始重新同步
+// 按路径排序分页获取, 响应不能超过 2MB
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryChangesSnapshot {
+    pub seq: u64, // 快照对应的序号, 使用第一页的序号, 之后从这里查询变更
+    pub files: Vec<QueryFile>,
+    pub next: Option<String>, // 下一页从该路径之后开始, 没有表示已经是最后一页
+}
+
+// 查询变更的错误
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub enum ChangesError {
+    Compacted { since: u64, compacted: u64 }, // 需要的变更已被压缩, 需要从快照重新同步
+    Unknown { since: u64, latest: u64 },      // 序号超过最新的序号, 罐子可能被重装过
+}
+impl Display for ChangesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangesError::Compacted { since, compacted } => {
+                write!(f, "changes since {since} are compacted up to {compacted}")
+            }
+            ChangesError::Unknown { since, latest } => {
+                write!(f, "unknown seq: {since} latest: {latest}")
+            }
+        }
+    }
+}
+impl std::error::Error for ChangesError {}
+
+impl InnerState {
+    fn log_change(
+        &mut self,
+        path: &str,
+        op: ChangeOp,
+        old_hash: Option<HashDigest>,
+        new_hash: Option<HashDigest>,
+        headers: Vec<(String, String)>,
+    ) {
+        match op {
+            ChangeOp::Put => self.counters.put_files += 1,
+            ChangeOp::PutEncoding(_) => self.counters.put_encodings += 1,
+            ChangeOp::Delete => self.counters.deleted_files += 1,
+            ChangeOp::DeleteEncoding(_) => self.counters.deleted_encodings += 1,
+        }
+        self.changes.push(AssetChange {
+            seq: 0,
+            path: path.to_string(),
+            op,
+            old_hash,
+            new_hash,
+            headers,
+            timestamp: ic_canister_kit::times::now(),
+            caller: ic_canister_kit::identity::caller(),
+        });
+    }
+    pub fn change_retention(&self) -> u64 {
+        self.changes.retention
+    }
+    // 保留的变更不能少于单次查询的数量, 否则镜像总是追不上
+    pub fn change_retention_update(&mut self, retention: u64) -> Result<(), String> {
+        if retention < MAX_CHANGES_LIMIT as u64 {
+            return Err(format!(
+                "change retention must be at least {MAX_CHANGES_LIMIT}: {retention}"
+            ));
+        }
+        self.changes.retention = retention;
+        self.changes.compact();
+        Ok(())
+    }
+    pub fn changes_since(&self, since: u64, limit: u32) -> Result<QueryChanges, ChangesError> {
+        self.changes.since(since, limit)
+    }
+    // 分页获取所有文件, after 是上一页的 next
+    pub fn changes_snapshot(&self, after: Option<String>, limit: u32) -> QueryChangesSnapshot {
+        let limit = limit.clamp(1, MAX_CHANGES_LIMIT) as usize;
+        let mut paths: Vec<&String> = self
+            .files
+            .keys()
+            .filter(|path| after.as_ref().map_or(true, |after| *after < **path))
+            .collect();
+        let mut next = None;
+        if limit < paths.len() {
+            paths.select_nth_unstable(limit - 1); // 只需要最小的 limit 个路径
+            paths.truncate(limit);
+            next = paths.iter().max().map(|path| path.to_string());
+        }
+        paths.sort_unstable();
+        QueryChangesSnapshot {
+            seq: self.changes.seq,
+            files: paths
+                .into_iter()
+                .filter_map(|path| self.file(path))
+                .collect(),
+            next,
+        }
+    }
+}
+
+// ============================== 同步副本 ==============================
+
+// 同步设置, 源罐子需要授予本罐子 BusinessCanisterRead 权限
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationSettings {
+    pub source: Option<CanisterId>, // 源罐子, 没有则不同步
+    pub batch: u32,                 // 每次拉取的变更数量
+}
+
+impl Default for ReplicationSettings {
+    fn default() -> Self {
+        Self {
+            source: None,
+            batch: 100,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Replication {
+    settings: ReplicationSettings,
+    cursor: u64,                             // 已经应用的源罐子变更序号
+    latest: u64,                             // 源罐子最新的变更序号
+    pulled: Option<TimestampNanos>,          // 最近一次成功拉取的时间
+    synced: Option<TimestampNanos>,          // 最近一次追上源罐子的时间
+    error: Option<(TimestampNanos, String)>, // 最近一次失败的原因
+    #[serde(skip)]
+    running: Option<TimestampNanos>, // 正在同步时最近一次有进展的时间, 避免定时任务重复执行
+}
+
+// 同步超过该时间没有进展, 认为上次同步已经中断 (异步调用后出错不会清除同步状态)
+const REPLICATE_TIMEOUT: i128 = 1_000_000_000 * 60 * 10;
+
+// 同步状态
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryReplication {
+    pub source: Option<CanisterId>,
+    pub cursor: u64,
+    pub latest: u64,
+    pub lag: u64, // 落后的变更数量
+    pub pulled: Option<TimestampNanos>,
+    pub synced: Option<TimestampNanos>,
+    pub error: Option<(TimestampNanos, String)>,
+    pub running: bool,
+}
+
+impl InnerState {
+    pub fn replication_settings(&self) -> ReplicationSettings {
+        self.replication.settings.clone()
+    }
+    // 更换源罐子后从头同步, 原来正在进行的同步结果会被丢弃
+    // 更新设置也会清除同步状态, 可以用来解除卡住的同步
+    pub fn replication_update(&mut self, settings: ReplicationSettings) {
+        if self.replication.settings.source != settings.source {
+            self.replication = Replication::default();
+        }
+        self.replication.settings = settings;
+        self.replication.running = None;
+    }
+    pub fn replication_status(&self) -> QueryReplication {
+        let r = &self.replication;
+        QueryReplication {
+            source: r.settings.source,
+            cursor: r.cursor,
+            latest: r.latest,
+            lag: r.latest.saturating_sub(r.cursor),
+            pulled: r.pulled,
+            synced: r.synced,
+            error: r.error.clone(),
+            running: r.running.is_some(),
+        }
+    }
+    // 开始同步, 返回源罐子和需要拉取的位置
+    pub fn replicate_begin(&mut self) -> Option<(CanisterId, u64, u32)> {
+        let now = ic_canister_kit::times::now();
+        let r = &mut self.replication;
+        let source = r.settings.source?;
+        if let Some(running) = r.running {
+            if now.into_inner() - running.into_inner() < REPLICATE_TIMEOUT {
+                return None;
+            }
+        }
+        r.running = Some(now);
+        Some((source, r.cursor, r.settings.batch.max(1)))
+    }
+    // 同步过程中源罐子可能被修改了, 结果需要丢弃
+    fn replicating(&mut self, source: &CanisterId) -> Result<(), String> {
+        let r = &mut self.replication;
+        if r.settings.source.as_ref() != Some(source) {
+            return Err(format!("replication source changed: {}", source.to_text()));
+        }
+        r.running = Some(ic_canister_kit::times::now()); // 有进展
+        Ok(())
+    }
+    pub fn replicate_end(&mut self, source: CanisterId, error: Option<String>) {
+        if self.replicating(&source).is_err() {
+            return;
+        }
+        let now = ic_canister_kit::times::now();
+        let r = &mut self.replication;
+        r.running = None;
+        match error {
+            Some(error) => r.error = Some((now, error)),
+            None => {
+                r.pulled = Some(now);
+                if r.latest <= r.cursor {
+                    r.synced = Some(now);
+                }
+            }
+        }
+        self.refresh_manifest();
+        self.notify_subscribers();
+    }
+    pub fn replicate_latest(&mut self, source: CanisterId, latest: u64) {
+        if self.replicating(&source).is_ok() {
+            self.replication.latest = latest;
+        }
+    }
+    // 被同一批中后面的变更替代的变更不需要应用, 直接移动游标
+    pub fn replicate_skip(&mut self, source: CanisterId, seq: u64) -> Result<(), String> {
+        self.replicating(&source)?;
+        self.replication.cursor = self.replication.cursor.max(seq);
+        Ok(())
+    }
+    // 应用源罐子的一条变更, 重复应用结果不变
+    // seq 为 0 的变更来自快照, 不移动游标
+    pub fn replicate_apply(
+        &mut self,
+        source: CanisterId,
+        change: AssetChange,
+        data: Option<Vec<u8>>,
+    ) -> Result<(), String> {
+        self.replicating(&source)?;
+        let AssetChange {
+            seq,
+            path,
+            op,
+            new_hash,
+            headers,
+            ..
+        } = change;
+        match (op, new_hash) {
+            (ChangeOp::Delete, _) => self.clean_file(&path),
+            (ChangeOp::DeleteEncoding(encoding), _) => self.delete_encoding(&path, &encoding),
+            (_, None) => return Err(format!("change {seq} of {path} has no hash")),
+            (ChangeOp::PutEncoding(encoding), Some(hash)) => {
+                let exist = self
+                    .files
+                    .get(&path)
+                    .map(|file| file.encodings.get(&encoding).map(|e| e.hash));
+                match exist {
+                    None => {} // 文件已经被删除, 编码数据没有意义
+                    Some(Some(exist)) if exist == hash => {}
+                    Some(_) => {
+                        let size = self.replicate_asset(&hash, data)?;
+                        self.put_encoding(path, encoding, hash, size);
+                    }
+                }
+            }
+            (ChangeOp::Put, Some(hash)) => {
+                let exist = self
+                    .files
+                    .get(&path)
+                    .map(|file| (file.hash, file.size, &file.headers));
+                match exist {
+                    Some((exist, size, exist_headers)) if exist == hash => {
+                        if *exist_headers != headers {
+                            self.log_change(
+                                &path,
+                                ChangeOp::Put,
+                                Some(hash),
+                                Some(hash),
+                                headers.clone(),
+                            );
+                            self.put_file(path, headers, hash, size);
+                        }
+                    }
+                    _ => {
+                        // 先保存并校验数据, 数据有误时保留原来的文件
+                        // ! put_file 替换原来的文件, 不会清除和旧编码相同的新数据
+                        let old = exist.map(|(old, _, _)| old);
+                        let size = self.replicate_asset(&hash, data)?;
+                        self.log_change(&path, ChangeOp::Put, old, Some(hash), headers.clone());
+                        self.put_file(path, headers, hash, size);
+                    }
+                }
+            }
+        }
+        if 0 < seq {
+            self.replication.cursor = seq;
+        }
+        Ok(())
+    }
+    // 保存拉取到的数据, 没有拉取数据时必须已经存在
+    fn replicate_asset(&mut self, hash: &HashDigest, data: Option<Vec<u8>>) -> Result<u64, String> {
+        if let Some(asset) = self.assets.get(hash) {
+            return Ok(asset.size() as u64);
+        }
+        let data = data.ok_or_else(|| format!("asset {} is not pulled", hash.hex()))?;
+        let actual = Self::hash_data(&data);
+        if actual != *hash {
+            return Err(format!(
+                "wrong asset hash: expected: {} actual: {}",
+                hash.hex(),
+                actual.hex()
+            ));
+        }
+        let size = data.len() as u64;
+        self.assets.insert(*hash, AssetData::from(hash, data));
+        Ok(size)
+    }
+    // 快照应用完毕, 删除源罐子没有的文件
+    pub fn replicate_snapshot_done(
+        &mut self,
+        source: CanisterId,
+        seq: u64,
+        paths: HashSet<String>,
+    ) -> Result<(), String> {
+        self.replicating(&source)?;
+        let removed: Vec<String> = self
+            .files
+            .keys()
+            .filter(|path| !paths.contains(*path))
+            .cloned()
+            .collect();
+        for path in removed {
+            self.clean_file(&path);
+        }
+        self.replication.cursor = seq;
+        self.replication.latest = self.replication.latest.max(seq);
+        Ok(())
+    }
+}
+
+// 同一批变更中被后面的变更替代的变更序号
+// 替代后的数据可能已经从源罐子删除了, 拉取不到, 只需要应用最后的变更
+pub fn superseded_changes(changes: &[AssetChange]) -> HashSet<u64> {
+    let mut files: HashSet<&str> = HashSet::new(); // 后面有 Put 或者 Delete 的路径
+    let mut encodings: HashSet<(&str, &str)> = HashSet::new(); // 后面有 PutEncoding 或者 DeleteEncoding 的编码
+    let mut superseded = HashSet::new();
+    for change in changes.iter().rev() {
+        let path = change.path.as_str();
+        let replaced = files.contains(path)
+            || matches!(&change.op, ChangeOp::PutEncoding(e) | ChangeOp::DeleteEncoding(e) if encodings.contains(&(path, e.as_str())));
+        if replaced {
+            superseded.insert(change.seq);
+        }
+        match &change.op {
+            ChangeOp::Put | ChangeOp::Delete => {
+                files.insert(path);
+            }
+            ChangeOp::PutEncoding(encoding) | ChangeOp::DeleteEncoding(encoding) => {
+                encodings.insert((path, encoding.as_str()));
+            }
+        }
+    }
+    superseded
+}
+
+// ============================== 订阅通知 ==============================
+
+// 订阅, 文件变化后单向调用订阅罐子的方法, 参数是 (vec AssetChange)
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    pub canister: CanisterId,
+    pub prefixes: Vec<String>, // 只通知这些前缀下的文件, 空表示所有文件
+    pub method: String,
+}
+
+impl Subscription {
+    fn matches(&self, path: &str) -> bool {
+        self.prefixes.is_empty() || self.prefixes.iter().any(|p| path.starts_with(p))
+    }
+}
+
+// 等待发送的通知
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Notifying {
+    canister: CanisterId,
+    method: String,
+    changes: Vec<AssetChange>,
+    attempts: u32, // 已经失败的次数
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Notifier {
+    notified: u64, // 该序号及之前的变更已经加入通知
+    queue: VecDeque<Notifying>,
+}
+
+// 失败后最多尝试的次数
+const MAX_NOTIFY_ATTEMPTS: u32 = 5;
+// 单次通知最多包含的变更数量
+const MAX_NOTIFY_CHANGES: usize = 500;
+
+impl InnerState {
+    pub fn subscriptions_update(&mut self, subscriptions: Vec<Subscription>) -> Result<(), String> {
+        for subscription in subscriptions.iter() {
+            if subscription.method.is_empty() {
+                return Err(format!(
+                    "method of {} can not be empty",
+                    subscription.canister.to_text()
+                ));
+            }
+        }
+        self.subscriptions = subscriptions;
+        self.notifier.notified = self.changes.seq; // 新的订阅不通知以前的变更
+        Ok(())
+    }
+    // 修改文件的接口结束后调用, 失败的通知在定时任务中重试
+    pub fn notify_subscribers(&mut self) {
+        // 1. 新的变更加入通知队列
+        let notified = self.notifier.notified;
+        self.notifier.notified = self.changes.seq;
+        for subscription in self.subscriptions.iter() {
+            let changes: Vec<AssetChange> = self
+                .changes
+                .changes
+                .iter()
+                .filter(|change| notified < change.seq && subscription.matches(&change.path))
+                .cloned()
+                .collect();
+            for changes in changes.chunks(MAX_NOTIFY_CHANGES) {
+                self.notifier.queue.push_back(Notifying {
+                    canister: subscription.canister,
+                    method: subscription.method.clone(),
+                    changes: changes.to_vec(),
+                    attempts: 0,
+                });
+            }
+        }
+
+        // 2. 发送通知, 单向调用不等待结果
+        let queue = std::mem::take(&mut self.notifier.queue);
+        for mut notifying in queue {
+            let result =
+                ic_cdk::notify(notifying.canister, &notifying.method, (&notifying.changes,));
+            let content = format!(
+                "notify {}.{} with {} changes",
+                notifying.canister.to_text(),
+                notifying.method,
+                notifying.changes.len()
+            );
+            match result {
+                Ok(_) if notifying.attempts == 0 => {}
+                Ok(_) => {
+                    self.record_notify(content, format!("retry {} succeeded", notifying.attempts))
+                }
+                Err(code) => {
+                    notifying.attempts += 1;
+                    if notifying.attempts < MAX_NOTIFY_ATTEMPTS {
+                        self.record_notify(
+                            content,
+                            format!(
+                                "attempt {} failed: {code:?}, will retry",
+                                notifying.attempts
+                            ),
+                        );
+                        self.notifier.queue.push_back(notifying);
+                    } else {
+                        self.record_notify(
+                            content,
+                            format!("attempt {} failed: {code:?}, dropped", notifying.attempts),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    fn record_notify(&mut self, content: String, done: String) {
+        let caller = ic_canister_kit::identity::self_canister_id();
+        let record_id = self.record_push(caller, RecordTopics::Notify.topic(), content);
+        self.record_update(record_id, done);
+    }
+}
+
+// ============================== 完整性审计 ==============================
+
+// 审计设置
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditSettings {
+    pub enabled: bool,
+    pub repair: bool,  // 是否修复发现的问题
+    pub interval: u64, // 两次审计之间的间隔 秒
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            repair: false,
+            interval: 60 * 60 * 24,
+        }
+    }
+}
+
+// 审计结果
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AuditReport {
+    pub started: Option<TimestampNanos>,
+    pub finished: Option<TimestampNanos>,
+    pub assets: u64,                               // 重新 hash 的数据数量
+    pub bytes: u64,                                // 重新 hash 的数据长度
+    pub mismatched: Vec<(HashDigest, HashDigest)>, // 内容和 hash 不一致的数据, (hash, 实际 hash)
+    pub orphan_assets: Vec<HashDigest>,            // 没有文件引用的数据
+    pub dangling_paths: Vec<(HashDigest, String)>, // hashes 中记录的路径没有引用该数据
+    pub unlinked_paths: Vec<(HashDigest, String)>, // 文件引用了数据, 但 hashes 中没有记录
+    pub missing_assets: Vec<(String, HashDigest)>, // 文件引用的数据不存在
+    pub repaired: bool,
+}
+
+// 审计进度, 数据太大时需要多次定时任务才能完成
+struct Auditing {
+    pending: Vec<HashDigest>,         // 等待重新 hash 的数据
+    verifying: Option<HashVerifying>, // 正在 hash 的数据
+    report: AuditReport,              // 审计中的结果
+}
+
+// 检查索引时每个条目的消耗, 折算为处理的字节数
+const AUDIT_INDEX_COST: usize = 256;
+
+impl InnerState {
+    pub fn audit_update(&mut self, audit: AuditSettings) {
+        if !audit.enabled {
+            self.auditing = None;
+        }
+        self.audit = audit;
+    }
+    pub fn audit_report(&self) -> Option<AuditReport> {
+        self.audit_report.clone()
+    }
+    // 定时任务中执行, 最多重新 hash budget 的数据, 完成后检查索引是否一致
+    pub fn audit_assets(&mut self, budget: &mut usize) {
+        use sha2::Digest;
+        if !self.audit.enabled {
+            return;
+        }
+
+        // 1. 是否需要开始新的审计
+        let now = ic_canister_kit::times::now();
+        let mut auditing = match self.auditing.take() {
+            Some(auditing) => auditing,
+            None => {
+                let due = self
+                    .audit_report
+                    .as_ref()
+                    .and_then(|report| report.finished)
+                    .map_or(true, |finished| {
+                        self.audit.interval as i128 * 1_000_000_000
+                            <= now.into_inner() - finished.into_inner()
+                    });
+                if !due {
+                    return;
+                }
+                Auditing {
+                    pending: self.assets.keys().cloned().collect(),
+                    verifying: None,
+                    report: AuditReport {
+                        started: Some(now),
+                        ..Default::default()
+                    },
+                }
+            }
+        };
+
+        // 2. 重新 hash 数据
+        while 0 < *budget {
+            let mut verifying = match auditing.verifying.take() {
+                Some(verifying) => verifying,
+                None => match auditing.pending.pop() {
+                    Some(hash) => HashVerifying::new(hash),
+                    None => break, // 全部完成
+                },
+            };
+            let asset = match self.assets.get(&verifying.hash) {
+                Some(asset) => asset,
+                None => continue, // 数据已经不存在了
+            };
+            let size = asset.size();
+            let offset_end = size.min(verifying.offset + *budget);
+            verifying.hasher.update(asset.slice(
+                &verifying.hash,
+                size as u64,
+                verifying.offset,
+                offset_end - verifying.offset,
+            ));
+            *budget -= offset_end - verifying.offset;
+            auditing.report.bytes += (offset_end - verifying.offset) as u64;
+            verifying.offset = offset_end;
+            if offset_end < size {
+                auditing.verifying = Some(verifying); // 下次继续
+                break;
+            }
+            let HashVerifying { hash, hasher, .. } = verifying;
+            let actual = HashDigest(hasher.finalize().into());
+            auditing.report.assets += 1;
+            if actual == hash {
+                self.claimed.remove(&hash); // 声称的 hash 已经确认正确
+            } else {
+                auditing.report.mismatched.push((hash, actual));
+            }
+        }
+        if auditing.verifying.is_some() || !auditing.pending.is_empty() {
+            self.auditing = Some(auditing);
+            return;
+        }
+
+        // 3. 检查索引是否一致, 剩余的处理量不够时下次再检查, 索引太多时单独占用一次定时任务
+        let cost = (self.assets.len() + self.hashes.len() + self.files.len()) * AUDIT_INDEX_COST;
+        if *budget < cost && *budget < TASK_BYTES_BUDGET {
+            self.auditing = Some(auditing);
+            return;
+        }
+        *budget = budget.saturating_sub(cost);
+        let mut report = auditing.report;
+        self.audit_indexes(&mut report);
+
+        // 4. 修复
+        if self.audit.repair {
+            self.audit_repair(&report);
+            report.repaired = true;
+        }
+
+        report.finished = Some(ic_canister_kit::times::now());
+        let record_id = self.record_push(
+            ic_canister_kit::identity::self_canister_id(),
+            RecordTopics::Security.topic(),
+            format!(
+                "audit: {} assets {} bytes mismatched: {} orphan assets: {} dangling paths: {} unlinked paths: {} missing assets: {}",
+                report.assets,
+                report.bytes,
+                report.mismatched.len(),
+                report.orphan_assets.len(),
+                report.dangling_paths.len(),
+                report.unlinked_paths.len(),
+                report.missing_assets.len(),
+            ),
+        );
+        self.record_update(
+            record_id,
+            if report.repaired {
+                "repaired"
+            } else {
+                "reported"
+            }
+            .into(),
+        );
+        self.audit_report = Some(report);
+    }
+    fn audit_indexes(&self, report: &mut AuditReport) {
+        // 文件引用的所有数据
+        let referenced = |path: &str, hash: &HashDigest| {
+            self.files.get(path).is_some_and(|file| {
+                file.hash == *hash || file.encodings.values().any(|e| e.hash == *hash)
+            })
+        };
+        for hash in self.assets.keys() {
+            let linked = self
+                .hashes
+                .get(hash)
+                .is_some_and(|HashedPath(paths)| paths.iter().any(|path| referenced(path, hash)));
+            if !linked {
+                report.orphan_assets.push(*hash);
+            }
+        }
+        for (hash, HashedPath(paths)) in self.hashes.iter() {
+            for path in paths.iter() {
+                if !referenced(path, hash) {
+                    report.dangling_paths.push((*hash, path.clone()));
+                }
+            }
+        }
+        for (path, file) in self.files.iter() {
+            let hashes =
+                std::iter::once(&file.hash).chain(file.encodings.values().map(|e| &e.hash));
+            for hash in hashes {
+                if !self.assets.contains_key(hash) {
+                    report.missing_assets.push((path.clone(), *hash));
+                }
+                if !self
+                    .hashes
+                    .get(hash)
+                    .is_some_and(|HashedPath(paths)| paths.contains(path))
+                {
+                    report.unlinked_paths.push((*hash, path.clone()));
+                }
+            }
+        }
+    }
+    fn audit_repair(&mut self, report: &AuditReport) {
+        // 1. 内容不一致的数据隔离, 等待校验的数据交给校验任务处理
+        for (hash, actual) in report.mismatched.iter() {
+            if !self.unverified.contains(hash) {
+                self.quarantine(*hash, *actual);
+            }
+        }
+        // 2. 补上缺失的索引
+        for (hash, path) in report.unlinked_paths.iter() {
+            let referenced = self.files.get(path).is_some_and(|file| {
+                file.hash == *hash || file.encodings.values().any(|e| e.hash == *hash)
+            });
+            if referenced {
+                self.hashes.entry(*hash).or_default().0.insert(path.clone());
+            }
+        }
+        // 3. 移除多余的索引
+        for (hash, path) in report.dangling_paths.iter() {
+            if let Some(HashedPath(paths)) = self.hashes.get_mut(hash) {
+                paths.remove(path);
+                if paths.is_empty() {
+                    self.hashes.remove(hash);
+                }
+            }
+        }
+        // 4. 数据不存在的文件或者编码无法提供服务, 需要移除
+        for (path, hash) in report.missing_assets.iter() {
+            if self.assets.contains_key(hash) {
+                continue;
+            }
+            let encodings: Vec<String> = match self.files.get(path) {
+                Some(file) if file.hash == *hash => {
+                    self.clean_file(path);
+                    vec![]
+                }
+                Some(file) => file
+                    .encodings
+                    .iter()
+                    .filter(|(_, e)| e.hash == *hash)
+                    .map(|(encoding, _)| encoding.clone())
+                    .collect(),
+                None => vec![],
+            };
+            for encoding in encodings {
+                self.delete_encoding(path, &encoding);
+            }
+            if let Some(HashedPath(paths)) = self.hashes.get_mut(hash) {
+                paths.remove(path);
+                if paths.is_empty() {
+                    self.hashes.remove(hash);
+                }
+            }
+        }
+        // 5. 没有引用的数据直接删除
+        for hash in report.orphan_assets.iter() {
+            if !self.hashes.contains_key(hash) {
+                self.assets.remove(hash);
+                self.unverify(hash);
+            }
+        }
+        self.manifest_cache = None; // 资源清单需要重新生成
+    }
+}
+
+// ============================== 存储统计 ==============================
+
+// 分组统计
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct StatsGroup {
+    pub name: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct StatsFile {
+    pub path: String,
+    pub size: u64,
+    pub hash: HashDigest,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub struct QueryStats {
+    pub files: u64,             // 文件数量
+    pub assets: u64,            // 去重后的数据数量
+    pub logical_bytes: u64,     // 所有文件大小之和
+    pub encoding_bytes: u64,    // 所有编码数据大小之和
+    pub physical_bytes: u64,    // 去重后的数据大小之和
+    pub uploading: u64,         // 上传中的文件数量
+    pub uploading_bytes: u64,   // 上传中占用的空间
+    pub quarantined_bytes: u64, // 隔离数据占用的空间
+    pub by_content_type: Vec<StatsGroup>,
+    pub by_prefix: Vec<StatsGroup>, // 按第一级目录统计
+    pub largest: Vec<StatsFile>,
+    pub heap_bytes: u64,   // 堆内存
+    pub stable_bytes: u64, // 稳定内存
+}
+
+// 最大文件的数量
+const STATS_LARGEST_FILES: usize = 20;
+
+// 按第一级目录统计, 如 /assets/a.js -> /assets/, 根目录下的文件是 /
+fn stats_prefix(path: &str) -> String {
+    let rest = path.strip_prefix('/').unwrap_or(path);
+    match rest.find('/') {
+        Some(index) => format!("/{}/", &rest[..index]),
+        None => "/".into(),
+    }
+}
+
+impl InnerState {
+    pub fn stats(&self) -> QueryStats {
+        let mut by_content_type: HashMap<String, StatsGroup> = HashMap::new();
+        let mut by_prefix: HashMap<String, StatsGroup> = HashMap::new();
+        let add = |groups: &mut HashMap<String, StatsGroup>, name: String, size: u64| {
+            let group = groups.entry(name.clone()).or_insert_with(|| StatsGroup {
+                name,
+                files: 0,
+                bytes: 0,
+            });
+            group.files += 1;
+            group.bytes += size;
+        };
+        for file in self.files.values() {
+            let content_type = file
+                .headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                .and_then(|(_, value)| value.split(';').next())
+                .map(|value| value.trim().to_lowercase())
+                .unwrap_or_else(|| "unknown".into());
+            add(&mut by_content_type, content_type, file.size);
+            add(&mut by_prefix, stats_prefix(&file.path), file.size);
+        }
+        let sorted = |groups: HashMap<String, StatsGroup>| {
+            let mut groups: Vec<StatsGroup> = groups.into_values().collect();
+            groups.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+            groups
+        };
+
+        let mut largest: Vec<&AssetFile> = self.files.values().collect();
+        largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+
+        QueryStats {
+            files: self.files.len() as u64,
+            assets: self.assets.len() as u64,
+            logical_bytes: self.files.values().map(|file| file.size).sum(),
+            encoding_bytes: self
+                .files
+                .values()
+                .flat_map(|file| file.encodings.values())
+                .map(|e| e.size)
+                .sum(),
+            physical_bytes: self.assets.values().map(|a| a.size() as u64).sum(),
+            uploading: self.uploading.len() as u64,
+            uploading_bytes: self.uploading.values().map(|f| f.data.len() as u64).sum(),
+            quarantined_bytes: self
+                .quarantined
+                .values()
+                .map(|q| q.data.size() as u64)
+                .sum(),
+            by_content_type: sorted(by_content_type),
+            by_prefix: sorted(by_prefix),
+            largest: largest
+                .into_iter()
+                .take(STATS_LARGEST_FILES)
+                .map(|file| StatsFile {
+                    path: file.path.clone(),
+                    size: file.size,
+                    hash: file.hash,
+                })
+                .collect(),
+            heap_bytes: heap_memory_size(),
+            stable_bytes: ic_cdk::api::stable::stable64_size() * 65536,
+        }
+    }
+}
+
+// 当前的堆内存大小
+fn heap_memory_size() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) as u64 * 65536
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+// ============================== 监控指标 ==============================
+
+// 监控指标的设置, 需要令牌或者调用者在允许列表中
+// ! 通过网关访问时调用者总是匿名身份, 网关抓取只能使用令牌
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MetricsSettings {
+    pub enabled: bool,
+    pub path: String,          // 访问路径
+    pub token: Option<String>, // 只支持 Authorization: Bearer <token>, 链接中的参数容易被日志记录
+    pub allowed: Vec<UserId>,  // 允许直接调用 http_request 查询的罐子或者用户, 对网关请求无效
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "/metrics".into(),
+            token: None,
+            allowed: vec![],
+        }
+    }
+}
+
+impl MetricsSettings {
+    // 记录时隐藏令牌
+    pub fn redacted(&self) -> Self {
+        Self {
+            token: self.token.as_ref().map(|_| "***".into()),
+            ..self.clone()
+        }
+    }
+    pub fn permitted(&self, caller: &UserId, token: Option<&str>) -> bool {
+        if let (Some(expected), Some(token)) = (&self.token, token) {
+            if !expected.is_empty() && constant_time_eq(expected.as_bytes(), token.as_bytes()) {
+                return true;
+            }
+        }
+        self.allowed.contains(caller)
+    }
+}
+
+// 比较令牌的耗时与内容无关, 避免逐字节猜测令牌
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0, |diff, (x, y)| diff | (x ^ y))
+        == 0
+}
+
+// 累计的事件数量, 升级后保留
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MetricsCounters {
+    pub put_files: u64,     // 新增或者替换的文件
+    pub put_encodings: u64, // 新增或者替换的编码数据
+    pub deleted_files: u64, // 删除的文件
+    #[serde(default)]
+    pub deleted_encodings: u64, // 删除的编码数据
+    pub upload_chunks: u64, // 上传的块
+    pub upload_bytes: u64,  // 上传的数据长度
+    pub quarantined: u64,   // 被隔离的数据
+}
+
+impl InnerState {
+    pub fn metrics_counters(&self) -> &MetricsCounters {
+        &self.counters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // InnerState::default 会调用 ic0 的打印接口, 测试中无法使用
+    fn state() -> InnerState {
+        InnerState {
+            canister_kit: Default::default(),
+            hashed: Default::default(),
+            verify: Default::default(),
+            assets: Default::default(),
+            files: Default::default(),
+            hashes: Default::default(),
+            uploading: Default::default(),
+            unverified: Default::default(),
+            claimed: Default::default(),
+            verifying: Default::default(),
+            quarantined: Default::default(),
+            compress_queue: Default::default(),
+            compressing: Default::default(),
+            routing: Default::default(),
+            error_pages: Default::default(),
+            maintenance: Default::default(),
+            route_rules: Default::default(),
+            header_rules: Default::default(),
+            globs: Default::default(),
+            cors_rules: Default::default(),
+            explore: Default::default(),
+            manifest: Default::default(),
+            manifest_cache: Default::default(),
+            changes: Default::default(),
+            replication: Default::default(),
+            subscriptions: Default::default(),
+            notifier: Default::default(),
+            audit: Default::default(),
+            audit_report: Default::default(),
+            auditing: Default::default(),
+            metrics: Default::default(),
+            counters: Default::default(),
+        }
+    }
+
+    fn asset_data(state: &InnerState, hash: &HashDigest) -> Vec<u8> {
+        state
+            .assets
+            .get(hash)
+            .map(|asset| {
+                asset
+                    .slice(hash, asset.size() as u64, 0, asset.size())
+                    .to_vec()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_store_asset_replaces_unverified_poison() {
+        let mut state = state();
+        state.hashed = true;
+        state.verify = true;
+
+        let honest = b"honest content".to_vec();
+        let hash = InnerState::hash_data(&honest);
+
+        // 1. 伪造的数据先声称了 hash
+        state.store_asset(hash, b"poisoned".to_vec());
+        assert!(state.unverified.contains(&hash));
+        assert_eq!(asset_data(&state, &hash), b"poisoned");
+
+        // 2. 另一份伪造的数据不能替换
+        state.store_asset(hash, b"another poison".to_vec());
+        assert_eq!(asset_data(&state, &hash), b"poisoned");
+        assert!(state.unverified.contains(&hash));
+
+        // 3. 正确的数据替换伪造的数据, 并且不再需要校验
+        state.store_asset(hash, honest.clone());
+        assert_eq!(asset_data(&state, &hash), honest);
+        assert!(!state.unverified.contains(&hash));
+
+        // 4. 已经校验的数据不会再被替换
+        state.store_asset(hash, b"late poison".to_vec());
+        assert_eq!(asset_data(&state, &hash), honest);
+    }
+
+    #[test]
+    fn test_claimed_hash_is_not_verified() {
+        let mut state = state();
+        state.hashed = true;
+
+        // 1. 不校验时保存的只是声称的 hash
+        let content = b"claimed content".to_vec();
+        let hash = InnerState::hash_data(&content);
+        state.store_asset(hash, content);
+        assert!(!state.unverified.contains(&hash));
+        assert!(!state.is_verified(&hash));
+
+        // 2. 关闭校验时没有校验完成的也只是声称的 hash
+        state.verify_update(true);
+        assert!(!state.is_verified(&hash));
+        state.verify_update(false);
+        assert!(!state.is_verified(&hash));
+
+        // 3. 校验完成后可信
+        state.verify_update(true);
+        let mut budget = TASK_BYTES_BUDGET;
+        state.verify_hashes(&mut budget);
+        assert!(state.is_verified(&hash));
+    }
+
+    fn uploading_arg(path: &str, chunk: &[u8], encoding: Option<&str>) -> UploadingArg {
+        UploadingArg {
+            path: path.into(),
+            headers: vec![],
+            hash: InnerState::hash_data(chunk),
+            size: chunk.len() as u64,
+            chunk_size: chunk.len() as u32,
+            index: 0,
+            chunk: chunk.to_vec(),
+            chunk_hash: Some(InnerState::hash_data(chunk)),
+            encoding: encoding.map(|e| e.into()),
+        }
+    }
+
+    #[test]
+    fn test_check_uploading_rejects_whole_batch() {
+        let state = state();
+        let good = uploading_arg("/a.txt", b"a", None);
+        let mut bad = uploading_arg("/b.txt", b"b", None);
+        bad.chunk_hash = Some(InnerState::hash_data(b"other"));
+        assert!(state.check_uploading(&[good.clone()]).is_ok());
+        assert!(matches!(
+            state.check_uploading(&[good.clone(), bad]),
+            Err(UploadingError::WrongChunkHash { path, .. }) if path == "/b.txt"
+        ));
+
+        // 编码数据可以依赖同一批中前面的文件
+        let gzip = uploading_arg("/a.txt", b"gz", Some("gzip"));
+        assert!(state.check_uploading(&[good, gzip.clone()]).is_ok());
+        assert!(matches!(
+            state.check_uploading(&[gzip.clone()]),
+            Err(UploadingError::FileNotFound(path)) if path == "/a.txt"
+        ));
+
+        // 前面的文件没有上传完整, 编码数据也不能保存
+        let mut part = uploading_arg("/a.txt", b"ab", None);
+        part.chunk_size = 1;
+        part.chunk = b"a".to_vec();
+        part.chunk_hash = None;
+        assert!(matches!(
+            state.check_uploading(&[part.clone(), gzip.clone()]),
+            Err(UploadingError::FileNotFound(path)) if path == "/a.txt"
+        ));
+        let mut rest = part.clone();
+        rest.index = 1;
+        rest.chunk = b"b".to_vec();
+        assert!(matches!(
+            state.check_uploading(&[part, rest, gzip]),
+            Ok(hashes) if hashes[1] == InnerState::hash_data(b"b")
+        ));
+    }
+
+    #[test]
+    fn test_routing_settings_ignores_root_index() -> Result<(), serde_json::Error> {
+        let legacy = r#"{"index_files":["index.html"],"fallback":null,"fallback_html_only":true,"root_index":true}"#;
+        let routing: RoutingSettings = serde_json::from_str(legacy)?;
+        assert_eq!(routing, RoutingSettings::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_captures() {
+        let globs = HashMap::new();
+        let glob = |g: &str, path: &str| PathPattern::Glob(g.into()).captures(path, &globs);
+        assert_eq!(glob("/docs/*.html", "/docs/a.html"), Some(vec!["a".into()]));
+        assert_eq!(glob("/docs/*.html", "/docs/a/b.html"), None); // * 不跨越 /
+        assert_eq!(
+            glob("/docs/**/*.html", "/docs/a/b/c.html"),
+            Some(vec!["a/b".into(), "c".into()])
+        );
+        assert_eq!(glob("/v?/a", "/v1/a"), Some(vec!["1".into()]));
+        assert_eq!(glob("/a.b", "/aXb"), None); // 其他字符需要转义
+        assert!(PathPattern::Glob("/a/*".into()).check().is_ok());
+    }
+
+    #[test]
+    fn test_substitute() {
+        let captures = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(substitute("/x/$1/$2", &captures), "/x/a/b");
+        assert_eq!(substitute("/x/$2$1", &captures), "/x/ba");
+        assert_eq!(substitute("/x/$3/$0/$", &captures), "/x/$3/$0/$"); // 没有的保持原样
+                                                                       // 捕获的内容中的 $n 不会再被替换
+        let captures = vec!["$2".to_string(), "b".to_string()];
+        assert_eq!(substitute("/$1/$2", &captures), "/$2/b");
+    }
+
+    #[test]
+    fn test_route_uses_compiled_globs() {
+        let mut state = state();
+        let rules = vec![
+            RouteRule {
+                pattern: PathPattern::Glob("/old/**".into()),
+                action: RouteAction::Redirect {
+                    status: 301,
+                    location: "/new/$1".into(),
+                },
+            },
+            RouteRule {
+                pattern: PathPattern::Prefix("/app/".into()),
+                action: RouteAction::Rewrite("/index.html?page=$1".into()),
+            },
+        ];
+        assert!(state.route_rules_update(rules).is_ok());
+        assert!(state.globs.contains_key("/old/**"));
+        assert_eq!(
+            state.route("/old/a/b"),
+            Some(RouteAction::Redirect {
+                status: 301,
+                location: "/new/a/b".into()
+            })
+        );
+        assert_eq!(
+            state.route("/app/x"),
+            Some(RouteAction::Rewrite("/index.html?page=x".into()))
+        );
+        assert_eq!(state.route("/other"), None);
+        // 重定向中的捕获内容重新转义, 不能带出换行
+        assert_eq!(
+            state.route("/old/a\rSet-Cookie: x"),
+            Some(RouteAction::Redirect {
+                status: 301,
+                location: "/new/a%0DSet-Cookie:%20x".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_find_error_page() {
+        let mut state = state();
+        let page = |prefix: &str, status: Option<u16>, path: &str| ErrorPage {
+            prefix: prefix.into(),
+            status,
+            path: path.into(),
+        };
+        state.error_pages = vec![
+            page("/", None, "/error.html"),
+            page("/", Some(404), "/404.html"),
+            page("/docs/", None, "/docs/error.html"),
+        ];
+        let find = |path: &str, status: u16| {
+            state
+                .find_error_page(path, status)
+                .map(|page| page.path.as_str())
+        };
+        assert_eq!(find("/a", 500), Some("/error.html"));
+        assert_eq!(find("/a", 404), Some("/404.html")); // 相同前缀时指定了响应码的优先
+        assert_eq!(find("/docs/a", 404), Some("/docs/error.html")); // 前缀最长的优先
+        state.error_pages.clear();
+        assert_eq!(state.find_error_page("/a", 404), None);
+    }
+
+    #[test]
+    fn test_asset_data_slice_truncates() {
+        let data = AssetData::from(&HashDigest([0; 32]), b"0123456789".to_vec());
+        let hash = HashDigest([0; 32]);
+        assert_eq!(&data.slice(&hash, 10, 2, 3)[..], b"234");
+        assert_eq!(&data.slice(&hash, 10, 8, 5)[..], b"89"); // 末尾截断
+        assert_eq!(&data.slice(&hash, 10, 12, 5)[..], b""); // 起始位置超出
+        assert_eq!(&data.slice(&hash, 4, 2, 5)[..], b"23"); // 按声明的长度截断
+        assert_eq!(&data.slice(&hash, 10, 2, usize::MAX)[..], b"23456789");
+    }
+
+    fn state_with_file(path: &str, content: &[u8]) -> InnerState {
+        let mut state = state();
+        let hash = InnerState::hash_data(content);
+        state.store_asset(hash, content.to_vec());
+        state.files.insert(
+            path.into(),
+            AssetFile {
+                path: path.into(),
+                created: 0.into(),
+                modified: 0.into(),
+                headers: vec![],
+                hash,
+                size: content.len() as u64,
+                encodings: Default::default(),
+            },
+        );
+        state
+    }
+
+    #[test]
+    fn test_download_by_keeps_range_contract() {
+        let state = state_with_file("/a.txt", b"0123456789");
+        assert_eq!(state.download_by("/a.txt".into(), 2, 3), b"234");
+        assert_eq!(state.download_by("/a.txt".into(), 0, 10), b"0123456789");
+        let out_of_range = |offset: u64, size: u64| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                state.download_by("/a.txt".into(), offset, size)
+            }))
+            .is_err()
+        };
+        assert!(out_of_range(8, 5)); // 旧接口超出范围仍然报错
+        assert!(out_of_range(10, 0));
+    }
+
+    fn change(path: &str) -> AssetChange {
+        AssetChange {
+            seq: 0,
+            path: path.into(),
+            op: ChangeOp::Put,
+            old_hash: None,
+            new_hash: None,
+            headers: vec![],
+            timestamp: 0.into(),
+            caller: candid::Principal::anonymous(),
+        }
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let mut log = ChangeLog::default();
+        assert!(
+            matches!(log.since(0, 10), Ok(QueryChanges { changes, latest: 0 }) if changes.is_empty())
+        );
+        for i in 0..5 {
+            log.push(change(&format!("/{i}")));
+        }
+        let seqs = |result: Result<QueryChanges, ChangesError>| {
+            result
+                .map(|q| q.changes.iter().map(|c| c.seq).collect::<Vec<_>>())
+                .ok()
+        };
+        assert_eq!(seqs(log.since(0, 10)), Some(vec![1, 2, 3, 4, 5]));
+        assert_eq!(seqs(log.since(2, 2)), Some(vec![3, 4]));
+        assert_eq!(seqs(log.since(5, 10)), Some(vec![]));
+        assert_eq!(seqs(log.since(0, 0)), Some(vec![1])); // 至少返回一条
+        assert!(matches!(
+            log.since(6, 10),
+            Err(ChangesError::Unknown {
+                since: 6,
+                latest: 5
+            })
+        ));
+
+        // 超出保留数量的变更被压缩
+        log.retention = 3;
+        log.compact();
+        assert!(matches!(
+            log.since(1, 10),
+            Err(ChangesError::Compacted {
+                since: 1,
+                compacted: 2
+            })
+        ));
+        assert_eq!(seqs(log.since(2, 10)), Some(vec![3, 4, 5]));
+    }
+
+    #[test]
+    fn test_change_retention_minimum() {
+        let mut state = state();
+        assert!(state.change_retention_update(0).is_err());
+        assert!(state
+            .change_retention_update(MAX_CHANGES_LIMIT as u64 - 1)
+            .is_err());
+        assert!(state
+            .change_retention_update(MAX_CHANGES_LIMIT as u64)
+            .is_ok());
+        assert_eq!(state.change_retention(), MAX_CHANGES_LIMIT as u64);
+    }
+
+    #[test]
+    fn test_superseded_changes() {
+        let changes: Vec<AssetChange> = [
+            ("/a", ChangeOp::Put),
+            ("/a", ChangeOp::PutEncoding("gzip".into())),
+            ("/b", ChangeOp::Put),
+            ("/a", ChangeOp::Put),
+            ("/b", ChangeOp::PutEncoding("gzip".into())),
+            ("/b", ChangeOp::PutEncoding("br".into())),
+            ("/b", ChangeOp::PutEncoding("gzip".into())),
+            ("/c", ChangeOp::Put),
+            ("/c", ChangeOp::Delete),
+            ("/d", ChangeOp::PutEncoding("gzip".into())),
+            ("/d", ChangeOp::DeleteEncoding("gzip".into())),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (path, op))| AssetChange {
+            seq: i as u64 + 1,
+            op,
+            ..change(path)
+        })
+        .collect();
+        let mut superseded: Vec<u64> = superseded_changes(&changes).into_iter().collect();
+        superseded.sort();
+        assert_eq!(superseded, vec![1, 2, 5, 8, 10]);
+    }
+
+    #[test]
+    fn test_changes_snapshot_pages() {
+        let mut state = state();
+        for i in 0..5 {
+            let path = format!("/{i}.txt");
+            let file = state_with_file(&path, path.as_bytes());
+            state.assets.extend(file.assets);
+            state.files.extend(file.files);
+        }
+        let mut after = None;
+        let mut pages = vec![];
+        loop {
+            let snapshot = state.changes_snapshot(after, 2);
+            pages.push(
+                snapshot
+                    .files
+                    .iter()
+                    .map(|f| f.path.clone())
+                    .collect::<Vec<_>>(),
+            );
+            after = snapshot.next;
+            if after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(
+            pages,
+            vec![
+                vec!["/0.txt", "/1.txt"],
+                vec!["/2.txt", "/3.txt"],
+                vec!["/4.txt"]
+            ]
+        );
+        assert!(state
+            .changes_snapshot(Some("/4.txt".into()), 2)
+            .files
+            .is_empty());
+    }
+
+    #[test]
+    fn test_verify_hashes_shares_budget() {
+        let mut state = state_with_file("/a.txt", b"0123456789");
+        let hash = InnerState::hash_data(b"0123456789");
+        state.unverified.insert(hash);
+
+        let mut budget = 4;
+        state.verify_hashes(&mut budget);
+        assert_eq!(budget, 0); // 处理量用完, 下次继续
+        assert!(state.unverified.contains(&hash));
+
+        let mut budget = 100;
+        state.verify_hashes(&mut budget);
+        assert_eq!(budget, 94); // 只扣除实际处理的数据
+        assert!(!state.unverified.contains(&hash));
+        assert!(state.assets.contains_key(&hash));
+    }
+
+    #[test]
+    fn test_stats_prefix() {
+        assert_eq!(stats_prefix("/assets/a.js"), "/assets/");
+        assert_eq!(stats_prefix("/assets/img/a.png"), "/assets/");
+        assert_eq!(stats_prefix("/index.html"), "/");
+        assert_eq!(stats_prefix("/"), "/");
+        assert_eq!(stats_prefix(""), "/"); // 不会越界
+        assert_eq!(stats_prefix("a/b"), "/a/");
+    }
+
+    #[test]
+    fn test_metrics_permitted() {
+        let caller = candid::Principal::anonymous();
+        let other = candid::Principal::management_canister();
+        let mut settings = MetricsSettings::default();
+        assert!(!settings.permitted(&caller, None));
+        assert!(!settings.permitted(&caller, Some("")));
+
+        settings.token = Some("secret".into());
+        assert!(settings.permitted(&caller, Some("secret")));
+        assert!(!settings.permitted(&caller, Some("secreT")));
+        assert!(!settings.permitted(&caller, Some("secret2")));
+        assert!(!settings.permitted(&caller, None));
+
+        settings.token = Some("".into()); // 空令牌无效
+        assert!(!settings.permitted(&caller, Some("")));
+
+        settings.allowed = vec![other];
+        assert!(settings.permitted(&other, None));
+        assert!(!settings.permitted(&caller, None));
+    }
+}
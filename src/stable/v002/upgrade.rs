@@ -0,0 +1,96 @@
+use super::super::v001::types::{
+    AssetData as LastAssetData, AssetFile as LastAssetFile, CanisterKit as LastCanisterKit,
+    HashDigest as LastHashDigest, InnerState as LastState, UploadingFile as LastUploadingFile,
+};
+
+use super::types::*;
+
+impl From<Box<LastState>> for Box<InnerState> {
+    fn from(value: Box<LastState>) -> Self {
+        let mut state = InnerState::default(); // ? 初始化
+
+        // ! 每次升级新版本，务必比较每一个数据的升级方式
+        // ! 如果不修改数据结构，可以直接赋值升级
+        // ! 如果修改数据结构，必须代码处理数据升级
+
+        let LastState {
+            canister_kit,
+            hashed,
+            assets,
+            files,
+            hashes,
+            uploading,
+        } = *value;
+
+        // 1. 继承之前的数据
+        let LastCanisterKit {
+            pause,
+            permissions,
+            records,
+            schedule,
+        } = canister_kit;
+        state.canister_kit.pause = pause;
+        state.canister_kit.permissions = permissions;
+        state.canister_kit.records = records;
+        state.canister_kit.schedule = schedule;
+
+        // 2. 业务数据
+        state.hashed = hashed;
+        state.assets = assets
+            .into_iter()
+            .map(|(hash, data)| {
+                let hash = upgrade_hash(hash);
+                (hash, upgrade_asset(&hash, data))
+            })
+            .collect();
+        state.files = files
+            .into_iter()
+            .map(|(path, file)| (path, upgrade_file(file)))
+            .collect();
+        state.hashes = hashes
+            .into_iter()
+            .map(|(hash, paths)| (upgrade_hash(hash), HashedPath(paths.0)))
+            .collect();
+        state.uploading = uploading
+            .into_iter()
+            .map(|(path, file)| (path, upgrade_uploading(file)))
+            .collect();
+
+        Box::new(state)
+    }
+}
+
+fn upgrade_hash(hash: LastHashDigest) -> HashDigest {
+    HashDigest(hash.0)
+}
+
+fn upgrade_asset(hash: &HashDigest, data: LastAssetData) -> AssetData {
+    AssetData::from(hash, data.into_inner())
+}
+
+fn upgrade_file(file: LastAssetFile) -> AssetFile {
+    AssetFile {
+        path: file.path,
+        created: file.created,
+        modified: file.modified,
+        headers: file.headers,
+        hash: upgrade_hash(file.hash),
+        size: file.size,
+        encodings: Default::default(),
+    }
+}
+
+fn upgrade_uploading(file: LastUploadingFile) -> UploadingFile {
+    UploadingFile {
+        path: file.path,
+        headers: file.headers,
+        hash: upgrade_hash(file.hash),
+        data: file.data,
+        size: file.size,
+        chunk_size: file.chunk_size,
+        chunk_hashes: vec![None; file.chunks as usize],
+        chunks: file.chunks,
+        chunked: file.chunked,
+        encoding: None,
+    }
+}
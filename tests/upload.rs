@@ -268,7 +268,8 @@ fn get_headers(file: &str) -> Vec<(String, String)> {
                         return content.to_string();
                     }
                 }
-                panic!("Unknown file type: {}", ext_str);
+                println!("Unknown file type: {}", ext_str);
+                String::new() // 未知类型由罐子推断
             }
 
             if &ext == "gz" {
@@ -395,14 +396,14 @@ fn parse_remote_files(output: String) -> Vec<RemoteFile> {
         let content = content.next().unwrap();
         // 解析 modified
         let modified: u64 = content
-            .split(r#" : int; hash = ""#)
+            .split(r#" : int; encodings = "#)
             .next()
             .unwrap()
             .to_string()
             .replace("_", "")
             .parse()
             .unwrap();
-        let mut content = content.split(r#" : int; hash = ""#);
+        let mut content = content.split(r#"}; hash = ""#); // 跳过 encodings
         content.next();
         let content = content.next().unwrap();
         // 解析 hash
@@ -605,7 +606,7 @@ fn do_upload_file(local_files: &Vec<UploadFile>, index: usize) {
             .iter()
             .map(|file| {
                 format!(
-                    "record{{ path=\"{}\"; headers=vec{{{}}}; hash=vec{{{}}}; size={}:nat64; chunk_size={}:nat32; index={}:nat32; chunk=vec{{{}}} }}",
+                    "record{{ path=\"{}\"; headers=vec{{{}}}; hash=vec{{{}}}; size={}:nat64; chunk_size={}:nat32; index={}:nat32; chunk=vec{{{}}}; chunk_hash=opt vec{{{}}} }}",
                     file.file.path,
                     file.file
                         .headers
@@ -617,7 +618,8 @@ fn do_upload_file(local_files: &Vec<UploadFile>, index: usize) {
                     file.file.size,
                     file.chunk_size,
                     file.index,
-                    (&file.file.data[file.offset..file.offset_end]).iter().map(|u|format!("{}:nat8", u)).collect::<Vec<String>>().join(";")
+                    (&file.file.data[file.offset..file.offset_end]).iter().map(|u|format!("{}:nat8", u)).collect::<Vec<String>>().join(";"),
+                    hex::decode(do_hash(&file.file.data[file.offset..file.offset_end].to_vec())).unwrap().iter().map(|u|format!("{}:nat8", u)).collect::<Vec<String>>().join(";")
                 )
             })
             .collect::<Vec<String>>()
@@ -681,7 +683,9 @@ fn do_upload_file_to_canister(arg: &str, local_files: &Vec<UploadFile>) -> Resul
         .duration_since(std::time::UNIX_EPOCH)
         .expect("Time went backwards");
 
-    if format!("{}", output.status).eq("exit status: 0") {
+    if format!("{}", output.status).eq("exit status: 0")
+        && !String::from_utf8_lossy(&output.stdout).contains("variant { Err")
+    {
         // 上传成功, 需要展示结果
         for file in local_files.iter() {
             println!(